@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+use std::process;
+
+use game::level::{Cell, LevelDescription, LevelParseError};
+use game::*;
+
+struct Diagnostic {
+    line: Option<usize>,
+    message: String,
+}
+
+fn main() {
+    let mut assets = PathBuf::from("assets");
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => {
+                assets = args
+                    .next()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| usage("--assets expects a directory"));
+            }
+            "-h" | "--help" => usage(""),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        let packs = LevelPacks::load_dir(Path::new(level_pack::PACKS_DIR));
+        paths = packs
+            .levels()
+            .map(|(_, level)| level.file.display().to_string())
+            .collect();
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let diagnostics = match check(Path::new(&path), &assets) {
+            Ok(diagnostics) => diagnostics,
+            Err(err) => {
+                eprintln!("{}", err);
+                failed = true;
+                continue;
+            }
+        };
+        for diagnostic in diagnostics.iter() {
+            match diagnostic.line {
+                Some(line) => eprintln!("{}:{}: {}", path, line, diagnostic.message),
+                None => eprintln!("{}: {}", path, diagnostic.message),
+            }
+        }
+
+        if diagnostics.is_empty() {
+            println!("{}: ok", path);
+        } else {
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("error: {}", error);
+    }
+    eprintln!("usage: lvl-check [--assets DIR] [LEVEL.lvl...]");
+    eprintln!("Checks every level in the level packs when no files are given.");
+    process::exit(2);
+}
+
+fn check(path: &Path, assets: &Path) -> Result<Vec<Diagnostic>, LevelParseError> {
+    let level = LevelDescription::load(path)?;
+
+    let mut diagnostics = Vec::new();
+    let line = |row: usize| Some(level.row_line(row));
+
+    if level.rows.len() != level.height as usize {
+        diagnostics.push(Diagnostic {
+            line: Some(1),
+            message: format!(
+                "header says the level is {} rows tall but the grid has {} rows",
+                level.height,
+                level.rows.len()
+            ),
+        });
+    }
+
+    for (row, cells) in level.rows.iter().enumerate() {
+        if cells.len() != level.width as usize {
+            diagnostics.push(Diagnostic {
+                line: line(row),
+                message: format!(
+                    "header says the level is {} columns wide but this row has {} columns",
+                    level.width,
+                    cells.len()
+                ),
+            });
+        }
+    }
+
+    let mut jimbos = Vec::new();
+    let mut orbs = 0;
+    // Whether a link with `(row, column)` in it also has a layer matching `linked`.
+    let linked_with = |row: usize, column: usize, linked: fn(&Cell) -> bool| {
+        level.links.iter().any(|link| {
+            link.cells.contains(&(row, column))
+                && link
+                    .cells
+                    .iter()
+                    .any(|(row, column)| level.rows[*row][*column].layers().into_iter().any(linked))
+        })
+    };
+    for (row, cells) in level.rows.iter().enumerate() {
+        for (column, layer) in cells
+            .iter()
+            .enumerate()
+            .flat_map(|(column, cell)| cell.layers().into_iter().map(move |layer| (column, layer)))
+        {
+            match layer {
+                Cell::Jimbo => jimbos.push(row),
+                Cell::Orb { .. } => orbs += 1,
+                Cell::Gate => {
+                    let linked = linked_with(row, column, |layer| {
+                        matches!(layer, Cell::Orb { .. } | Cell::Plate(_))
+                    });
+                    if !linked {
+                        diagnostics.push(Diagnostic {
+                            line: line(row),
+                            message: format!(
+                                "column {}: gate is not linked to any orb or plate, so it never opens",
+                                column + 1
+                            ),
+                        });
+                    }
+                }
+                Cell::Plate(_) => {
+                    let linked = linked_with(row, column, |layer| {
+                        matches!(layer, Cell::Gate | Cell::LaserSource { .. })
+                    });
+                    if !linked {
+                        diagnostics.push(Diagnostic {
+                            line: line(row),
+                            message: format!(
+                                "column {}: plate is not linked to any gate or laser source, so it does nothing",
+                                column + 1
+                            ),
+                        });
+                    }
+                }
+                Cell::Refactor(kind) => {
+                    let texture = assets.join(kind.material_name());
+                    if !texture.is_file() {
+                        diagnostics.push(Diagnostic {
+                            line: line(row),
+                            message: format!(
+                                "column {}: refactor `{}` has no texture, expected {}",
+                                column + 1,
+                                layer.token(),
+                                texture.display()
+                            ),
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    match jimbos.len() {
+        1 => (),
+        0 => diagnostics.push(Diagnostic {
+            line: None,
+            message: "level has no Jimbo (`P`)".to_string(),
+        }),
+        _ => {
+            for row in jimbos {
+                diagnostics.push(Diagnostic {
+                    line: line(row),
+                    message: "level has more than one Jimbo (`P`)".to_string(),
+                });
+            }
+        }
+    }
+
+    if orbs == 0 {
+        diagnostics.push(Diagnostic {
+            line: None,
+            message: "level has no orbs, so it is complete before the first move".to_string(),
+        });
+    }
+
+    Ok(diagnostics)
+}
@@ -0,0 +1,175 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::process;
+
+use game::game_state::{ObjectId, ObjectKind};
+use game::level::LevelDescription;
+use game::*;
+
+const DEFAULT_MAX_STATES: usize = 1_000_000;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+];
+
+enum Solution {
+    Solved(Vec<Direction>),
+    Unsolvable { explored: usize },
+    GaveUp { explored: usize },
+}
+
+fn main() {
+    let mut max_states = DEFAULT_MAX_STATES;
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-states" => {
+                max_states = args
+                    .next()
+                    .and_then(|max| max.parse().ok())
+                    .unwrap_or_else(|| usage("--max-states expects a number"));
+            }
+            "-h" | "--help" => usage(""),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        let packs = LevelPacks::load_dir(Path::new(level_pack::PACKS_DIR));
+        paths = packs
+            .levels()
+            .map(|(_, level)| level.file.display().to_string())
+            .collect();
+    }
+
+    let mut all_solved = true;
+    for path in paths {
+        let level = match LevelDescription::load(Path::new(&path)) {
+            Ok(level) => level,
+            Err(err) => {
+                eprintln!("{}", err);
+                all_solved = false;
+                continue;
+            }
+        };
+
+        match solve(GameState::new(&level), max_states) {
+            Solution::Solved(moves) => {
+                let count = moves.len();
+                let mut state = GameState::new(&level);
+                let moves = moves
+                    .into_iter()
+                    .filter_map(|direction| lurd::step_char(&state.step(direction)))
+                    .collect::<String>();
+                println!("{}: solved in {} moves: {}", path, count, moves);
+            }
+            Solution::Unsolvable { explored } => {
+                println!("{}: unsolvable ({} states explored)", path, explored);
+                all_solved = false;
+            }
+            Solution::GaveUp { explored } => {
+                println!("{}: gave up after {} states", path, explored);
+                all_solved = false;
+            }
+        }
+    }
+
+    if !all_solved {
+        process::exit(1);
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("error: {}", error);
+    }
+    eprintln!("usage: solve [--max-states N] [LEVEL.lvl...]");
+    eprintln!("Solves every level in the level packs when no files are given.");
+    process::exit(2);
+}
+
+/// Breadth first search over moves, so the first solution found is a shortest one.
+fn solve(initial: GameState, max_states: usize) -> Solution {
+    if initial.is_complete() {
+        return Solution::Solved(Vec::new());
+    }
+
+    // Each explored state remembers the state it came from and the move that got there.
+    let mut explored: Vec<(usize, Direction)> = vec![(0, Direction::Down)];
+    let mut seen = HashSet::new();
+    seen.insert(state_key(&initial));
+    let mut queue = VecDeque::new();
+    queue.push_back((0, initial));
+
+    while let Some((index, state)) = queue.pop_front() {
+        for direction in DIRECTIONS.iter() {
+            let mut next = state.clone();
+            let outcome = next.step(*direction);
+            if outcome.moved.is_empty() || has_destroyed_orb(&next) {
+                continue;
+            }
+            if !seen.insert(state_key(&next)) {
+                continue;
+            }
+
+            explored.push((index, *direction));
+            let next_index = explored.len() - 1;
+            if outcome.completed {
+                return Solution::Solved(moves_to(&explored, next_index));
+            }
+            if explored.len() >= max_states {
+                return Solution::GaveUp {
+                    explored: explored.len(),
+                };
+            }
+            queue.push_back((next_index, next));
+        }
+    }
+
+    Solution::Unsolvable {
+        explored: explored.len(),
+    }
+}
+
+/// The coordinates of everything that can move, plus which gates are open and which laser sources
+/// are on. Positions alone aren't enough, since a gate lit through by its own orb's beam stays open
+/// where a shut one would stay shut. With destroyed orbs pruned, orb states follow from the rest.
+fn state_key(state: &GameState) -> (Vec<Coordinate>, Vec<(ObjectId, bool)>) {
+    let snapshot = state.snapshot();
+    let coordinates = state
+        .objects()
+        .iter()
+        .zip(snapshot.coordinates)
+        .filter(|(object, _)| object.kind.movable() != Some(false))
+        .map(|(_, coordinate)| coordinate)
+        .collect();
+    (coordinates, snapshot.switches)
+}
+
+/// Destroyed orbs never come back, so there is no point searching past them.
+fn has_destroyed_orb(state: &GameState) -> bool {
+    state.objects().iter().any(|object| {
+        matches!(
+            object.kind,
+            ObjectKind::Orb {
+                state: OrbState::Destroyed,
+                ..
+            }
+        )
+    })
+}
+
+fn moves_to(explored: &[(usize, Direction)], mut index: usize) -> Vec<Direction> {
+    let mut moves = Vec::new();
+    while index != 0 {
+        let (parent, direction) = explored[index];
+        moves.push(direction);
+        index = parent;
+    }
+    moves.reverse();
+    moves
+}
@@ -1,9 +1,9 @@
 use bevy::prelude::*;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
 
 use crate::*;
 
-#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Coordinate {
     pub x: i32,
     pub y: i32,
@@ -58,7 +58,7 @@ impl SubAssign<IVec2> for Coordinate {
 #[derive(Debug, Copy, Clone)]
 pub struct Crate;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Right,
@@ -113,31 +113,74 @@ impl Direction {
     }
 }
 
+#[derive(Debug, Copy, Clone)]
+pub struct Filter;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Gate {
+    pub open: bool,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Jimbo;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum LaserType {
-    Blue,
-    Purple,
-    Red,
+#[derive(Debug, Copy, Clone)]
+pub struct Plate {
+    pub pressed: bool,
 }
 
+/// The color of a beam as a set of red, green and blue light. Beams meeting at a refactor add
+/// together, so red and green leave it yellow and all three leave it white.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LaserType(u8);
+
 impl LaserType {
-    pub fn amalgamate(set: &HashSet<LaserType>) -> LaserType {
-        match (
-            set.contains(&LaserType::Red),
-            set.contains(&LaserType::Blue),
-            set.contains(&LaserType::Purple),
-        ) {
-            (true, true, true)
-            | (true, true, false)
-            | (true, false, true)
-            | (false, true, true)
-            | (false, false, true) => LaserType::Purple,
-            (true, false, false) => LaserType::Red,
-            (false, true, false) => LaserType::Blue,
-            (false, false, false) => unimplemented!("2"),
+    pub const RED: LaserType = LaserType(0b001);
+    pub const GREEN: LaserType = LaserType(0b010);
+    pub const BLUE: LaserType = LaserType(0b100);
+    pub const YELLOW: LaserType = LaserType(0b011);
+    pub const MAGENTA: LaserType = LaserType(0b101);
+    pub const CYAN: LaserType = LaserType(0b110);
+    pub const WHITE: LaserType = LaserType(0b111);
+
+    /// Every color, in the order the editor cycles through them.
+    pub const ALL: [LaserType; 7] = [
+        LaserType::RED,
+        LaserType::GREEN,
+        LaserType::BLUE,
+        LaserType::YELLOW,
+        LaserType::MAGENTA,
+        LaserType::CYAN,
+        LaserType::WHITE,
+    ];
+
+    pub fn mix(self, other: LaserType) -> LaserType {
+        LaserType(self.0 | other.0)
+    }
+
+    /// The light the two colors share, or `None` if they have nothing in common.
+    pub fn intersection(self, other: LaserType) -> Option<LaserType> {
+        match self.0 & other.0 {
+            0 => None,
+            shared => Some(LaserType(shared)),
+        }
+    }
+
+    /// Whether all the light in `other` is also in this color.
+    pub fn contains(self, other: LaserType) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The color beams and orbs are drawn in.
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            LaserType::RED => [232, 39, 39],
+            LaserType::GREEN => [38, 196, 62],
+            LaserType::BLUE => [24, 107, 255],
+            LaserType::YELLOW => [240, 208, 32],
+            LaserType::MAGENTA => [200, 32, 200],
+            LaserType::CYAN => [32, 212, 224],
+            _ => [240, 240, 240],
         }
     }
 }
@@ -146,11 +189,13 @@ impl LaserType {
 pub struct LaserSource {
     pub direction: crate::Direction,
     pub laser_type: LaserType,
+    pub on: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct Laser {
     pub source: Entity,
+    pub direction: crate::Direction,
     pub laser_type: LaserType,
     pub end: Coordinate,
 }
@@ -158,13 +203,16 @@ pub struct Laser {
 #[derive(Debug, Copy, Clone)]
 pub struct LevelObject;
 
+#[derive(Debug, Copy, Clone)]
+pub struct Mirror;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Movable(pub bool);
 
 #[derive(Debug, Copy, Clone)]
 pub struct Opaque;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OrbState {
     Deactivated,
     Activated,
@@ -180,7 +228,6 @@ pub struct Orb {
 #[derive(Debug, Clone)]
 pub struct RefactorDirection {
     pub direction: crate::Direction,
-    pub inbound_lasers: HashSet<LaserType>,
     pub outbound_laser: Entity,
 }
 
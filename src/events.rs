@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::*;
 
@@ -7,8 +8,26 @@ pub struct EventPlugin;
 impl Plugin for EventPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_event::<AppStateChangeEvent>();
+        app.add_event::<LevelCommand>();
+        app.add_event::<LevelCompletedEvent>();
     }
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct AppStateChangeEvent(pub AppState);
+
+/// Something the player does within a level, whichever device it came from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelCommand {
+    Move(crate::Direction),
+    Undo,
+    Redo,
+    /// Puts the level back how it was loaded, as a turn that can itself be undone.
+    Restart,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct LevelCompletedEvent {
+    pub level: usize,
+    pub moves: usize,
+}
@@ -0,0 +1,968 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::level::{Cell, FilterMode, LevelDescription, MirrorKind, OrbMatch, RefactorKind};
+use crate::{Coordinate, Direction, LaserType, OrbState};
+
+/// Refactors can feed each other, so beam tracing is repeated until it settles. Levels never get
+/// close to this many chained refactors; the cap only guards against beams that cycle.
+const MAX_TRACE_PASSES: usize = 64;
+
+const ALL_DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+];
+
+/// Index of an object in a `GameState`. Spawned entities carry it so the view can find its model.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ObjectId(pub usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectKind {
+    Wall,
+    Crate,
+    Jimbo,
+    Orb {
+        orb_type: LaserType,
+        rule: OrbMatch,
+        state: OrbState,
+    },
+    Refactor(RefactorKind),
+    LaserSource {
+        laser_type: LaserType,
+        direction: Direction,
+        on: bool,
+    },
+    Mirror {
+        kind: MirrorKind,
+        fixed: bool,
+    },
+    Filter {
+        laser_type: LaserType,
+        mode: FilterMode,
+        fixed: bool,
+    },
+    Gate {
+        open: bool,
+    },
+    /// Pressed while anything else is on it.
+    Plate,
+}
+
+impl ObjectKind {
+    /// `None` for objects that neither get pushed nor stop a push.
+    pub fn movable(&self) -> Option<bool> {
+        match self {
+            Self::Crate | Self::Refactor(_) | Self::LaserSource { .. } => Some(true),
+            Self::Wall | Self::Orb { .. } => Some(false),
+            Self::Mirror { fixed, .. } | Self::Filter { fixed, .. } => Some(!fixed),
+            Self::Gate { open: false } => Some(false),
+            Self::Jimbo | Self::Gate { open: true } | Self::Plate => None,
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        !matches!(
+            self,
+            Self::Refactor(_)
+                | Self::Mirror { .. }
+                | Self::Filter { .. }
+                | Self::Gate { open: true }
+                | Self::Plate
+        )
+    }
+
+    /// Whether a gate is open or a laser source is on, for the objects links switch.
+    pub fn switched_on(&self) -> Option<bool> {
+        match *self {
+            Self::Gate { open } => Some(open),
+            Self::LaserSource { on, .. } => Some(on),
+            _ => None,
+        }
+    }
+
+    fn switch(&mut self, switched_on: bool) {
+        match self {
+            Self::Gate { open } => *open = switched_on,
+            Self::LaserSource { on, .. } => *on = switched_on,
+            _ => (),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    pub kind: ObjectKind,
+    pub coordinate: Coordinate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Beam {
+    /// The laser source or refactor emitting the beam.
+    pub source: ObjectId,
+    pub direction: Direction,
+    pub laser_type: LaserType,
+    pub start: Coordinate,
+    /// The mirrors the beam bounced off, in order.
+    pub bends: Vec<Coordinate>,
+    /// Where the beam stopped, which is just outside the level if nothing blocked it.
+    pub end: Coordinate,
+    /// The direction the beam was heading when it stopped, which mirrors can make differ from
+    /// `direction`.
+    pub end_direction: Direction,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OrbChange {
+    pub orb: ObjectId,
+    pub from: OrbState,
+    pub to: OrbState,
+}
+
+/// A gate opening or closing, or a laser source turning on or off.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SwitchChange {
+    pub object: ObjectId,
+    pub on: bool,
+}
+
+/// Everything a turn can change, which is enough to put a level back the way it was.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Indexed by `ObjectId`.
+    pub coordinates: Vec<Coordinate>,
+    pub orbs: Vec<(ObjectId, OrbState)>,
+    /// Whether each gate is open and each laser source is on. Missing from snapshots saved before
+    /// there was anything to switch.
+    #[serde(default)]
+    pub switches: Vec<(ObjectId, bool)>,
+    pub facing: Direction,
+}
+
+/// Everything a single step changed, which is also enough to revert it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepOutcome {
+    pub direction: Direction,
+    pub previous_facing: Direction,
+    /// Objects moved one tile in `direction`, starting with Jimbo. Empty if the move was blocked.
+    pub moved: Vec<ObjectId>,
+    /// In the order they happened, which can include an orb changing more than once as gates
+    /// open and close.
+    pub orb_changes: Vec<OrbChange>,
+    pub switch_changes: Vec<SwitchChange>,
+    pub completed: bool,
+}
+
+impl StepOutcome {
+    pub fn pushed(&self) -> bool {
+        self.moved.len() > 1
+    }
+}
+
+/// The rules of a level without any rendering: pushing, beam tracing, orb activation and the gates
+/// and laser sources switched by orbs and plates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameState {
+    width: u32,
+    height: u32,
+    objects: Vec<Object>,
+    jimbo: Option<ObjectId>,
+    facing: Direction,
+    beams: Vec<Beam>,
+    /// The orbs and plates switching each linked gate and laser source.
+    triggers: HashMap<ObjectId, Vec<ObjectId>>,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState {
+            width: 0,
+            height: 0,
+            objects: Vec::new(),
+            jimbo: None,
+            facing: Direction::Down,
+            beams: Vec::new(),
+            triggers: HashMap::new(),
+        }
+    }
+}
+
+impl GameState {
+    /// Objects are numbered in file order, skipping empty cells, with a plate coming before
+    /// whatever starts on it.
+    pub fn new(level: &LevelDescription) -> GameState {
+        let objects = level
+            .cells()
+            .flat_map(|(coordinate, cell)| {
+                cell.layers().into_iter().filter_map(move |layer| {
+                    let kind = match *layer {
+                        Cell::Empty => return None,
+                        Cell::Wall => ObjectKind::Wall,
+                        Cell::Crate => ObjectKind::Crate,
+                        Cell::Jimbo => ObjectKind::Jimbo,
+                        Cell::Orb { orb_type, rule } => ObjectKind::Orb {
+                            orb_type,
+                            rule,
+                            state: OrbState::Deactivated,
+                        },
+                        Cell::Refactor(kind) => ObjectKind::Refactor(kind),
+                        Cell::LaserSource {
+                            laser_type,
+                            direction,
+                        } => ObjectKind::LaserSource {
+                            laser_type,
+                            direction,
+                            on: true,
+                        },
+                        Cell::Mirror { kind, fixed } => ObjectKind::Mirror { kind, fixed },
+                        Cell::Filter {
+                            laser_type,
+                            mode,
+                            fixed,
+                        } => ObjectKind::Filter {
+                            laser_type,
+                            mode,
+                            fixed,
+                        },
+                        Cell::Gate => ObjectKind::Gate { open: false },
+                        Cell::Plate(_) => ObjectKind::Plate,
+                    };
+                    Some(Object { kind, coordinate })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let jimbo = objects
+            .iter()
+            .position(|object| object.kind == ObjectKind::Jimbo)
+            .map(ObjectId);
+
+        // Nothing has moved yet, so each linked cell holds just the objects spawned from it.
+        let mut triggers: HashMap<ObjectId, Vec<ObjectId>> = HashMap::new();
+        for link in level.links.iter() {
+            let (switched, group_triggers): (Vec<_>, Vec<_>) = link
+                .cells
+                .iter()
+                .flat_map(|(row, column)| {
+                    let coordinate = level.coordinate(*row, *column);
+                    objects
+                        .iter()
+                        .enumerate()
+                        .filter(move |(_, object)| object.coordinate == coordinate)
+                        .map(|(index, _)| ObjectId(index))
+                })
+                .filter(|id| {
+                    matches!(
+                        objects[id.0].kind,
+                        ObjectKind::Gate { .. }
+                            | ObjectKind::LaserSource { .. }
+                            | ObjectKind::Orb { .. }
+                            | ObjectKind::Plate
+                    )
+                })
+                .partition(|id| objects[id.0].kind.switched_on().is_some());
+            for id in switched {
+                triggers
+                    .entry(id)
+                    .or_default()
+                    .extend(group_triggers.iter().copied());
+            }
+        }
+
+        let mut state = GameState {
+            width: level.width,
+            height: level.height,
+            objects,
+            jimbo,
+            triggers,
+            ..Default::default()
+        };
+        state.settle();
+        state
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn object(&self, id: ObjectId) -> &Object {
+        &self.objects[id.0]
+    }
+
+    pub fn jimbo(&self) -> Option<ObjectId> {
+        self.jimbo
+    }
+
+    pub fn facing(&self) -> Direction {
+        self.facing
+    }
+
+    pub fn beams(&self) -> &[Beam] {
+        &self.beams
+    }
+
+    pub fn beam(&self, source: ObjectId, direction: Direction) -> Option<&Beam> {
+        self.beams
+            .iter()
+            .find(|beam| beam.source == source && beam.direction == direction)
+    }
+
+    pub fn in_bounds(&self, coordinate: Coordinate) -> bool {
+        coordinate.x >= 0
+            && coordinate.x < self.width as i32
+            && coordinate.y >= 0
+            && coordinate.y < self.height as i32
+    }
+
+    /// The level is complete once every orb is activated.
+    pub fn is_complete(&self) -> bool {
+        self.objects.iter().all(|object| match object.kind {
+            ObjectKind::Orb { state, .. } => state == OrbState::Activated,
+            _ => true,
+        })
+    }
+
+    /// Whether anything besides the plate itself is on a plate.
+    pub fn is_pressed(&self, plate: ObjectId) -> bool {
+        let coordinate = self.object(plate).coordinate;
+        self.objects
+            .iter()
+            .enumerate()
+            .any(|(index, object)| index != plate.0 && object.coordinate == coordinate)
+    }
+
+    /// Moves Jimbo one tile, pushing any movable objects in the way, then updates beams and orbs.
+    /// A blocked move still turns Jimbo to face `direction`.
+    pub fn step(&mut self, direction: Direction) -> StepOutcome {
+        let previous_facing = self.facing;
+        self.facing = direction;
+
+        let mut outcome = StepOutcome {
+            direction,
+            previous_facing,
+            moved: Vec::new(),
+            orb_changes: Vec::new(),
+            switch_changes: Vec::new(),
+            completed: false,
+        };
+
+        let jimbo = match self.jimbo {
+            Some(jimbo) => jimbo,
+            None => return outcome,
+        };
+
+        let occupants = self.occupants();
+        let offset = direction.direction();
+        let mut check_coordinate = self.object(jimbo).coordinate + offset;
+        let mut move_objects = vec![jimbo];
+        'outer: while let Some(ids) = occupants.get(&check_coordinate) {
+            let mut has_movable = false;
+            for id in ids {
+                match self.object(*id).kind.movable() {
+                    Some(false) => {
+                        move_objects.clear();
+                        break 'outer;
+                    }
+                    Some(true) => {
+                        has_movable = true;
+                        move_objects.push(*id);
+                    }
+                    None => (),
+                }
+            }
+            if !has_movable {
+                break;
+            }
+            check_coordinate += offset;
+        }
+
+        if !self.in_bounds(check_coordinate) {
+            move_objects.clear();
+        }
+
+        for id in move_objects.iter() {
+            self.objects[id.0].coordinate += offset;
+        }
+        outcome.moved = move_objects;
+
+        let (orb_changes, switch_changes) = self.settle();
+        outcome.orb_changes = orb_changes;
+        outcome.switch_changes = switch_changes;
+        outcome.completed = self.is_complete();
+        outcome
+    }
+
+    /// Puts everything back the way it was before the step that produced `outcome`.
+    pub fn revert(&mut self, outcome: &StepOutcome) {
+        let offset = outcome.direction.direction();
+        for id in outcome.moved.iter() {
+            self.objects[id.0].coordinate -= offset;
+        }
+
+        // Backwards, so anything that changed more than once ends up how it started.
+        for change in outcome.orb_changes.iter().rev() {
+            if let ObjectKind::Orb { state, .. } = &mut self.objects[change.orb.0].kind {
+                *state = change.from;
+            }
+        }
+
+        for change in outcome.switch_changes.iter().rev() {
+            self.objects[change.object.0].kind.switch(!change.on);
+        }
+
+        self.facing = outcome.previous_facing;
+        self.trace_beams();
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let orbs = self
+            .objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, object)| match object.kind {
+                ObjectKind::Orb { state, .. } => Some((ObjectId(index), state)),
+                _ => None,
+            })
+            .collect();
+        let switches = self
+            .objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, object)| {
+                object
+                    .kind
+                    .switched_on()
+                    .map(|switched_on| (ObjectId(index), switched_on))
+            })
+            .collect();
+
+        Snapshot {
+            coordinates: self
+                .objects
+                .iter()
+                .map(|object| object.coordinate)
+                .collect(),
+            orbs,
+            switches,
+            facing: self.facing,
+        }
+    }
+
+    /// Panics if the snapshot was taken of a different level.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        assert_eq!(
+            snapshot.coordinates.len(),
+            self.objects.len(),
+            "snapshot is from a different level"
+        );
+
+        for (object, coordinate) in self.objects.iter_mut().zip(snapshot.coordinates.iter()) {
+            object.coordinate = *coordinate;
+        }
+
+        for (id, orb_state) in snapshot.orbs.iter() {
+            if let ObjectKind::Orb { state, .. } = &mut self.objects[id.0].kind {
+                *state = *orb_state;
+            }
+        }
+
+        for (id, switched_on) in snapshot.switches.iter() {
+            self.objects[id.0].kind.switch(*switched_on);
+        }
+
+        self.facing = snapshot.facing;
+        self.trace_beams();
+    }
+
+    fn occupants(&self) -> HashMap<Coordinate, Vec<ObjectId>> {
+        let mut occupants: HashMap<Coordinate, Vec<ObjectId>> = HashMap::new();
+        for (index, object) in self.objects.iter().enumerate() {
+            occupants
+                .entry(object.coordinate)
+                .or_default()
+                .push(ObjectId(index));
+        }
+        occupants
+    }
+
+    /// Traces beams and updates orbs, then does it again for as long as that switches gates or laser
+    /// sources.
+    fn settle(&mut self) -> (Vec<OrbChange>, Vec<SwitchChange>) {
+        let mut orb_changes = Vec::new();
+        let mut switch_changes = Vec::new();
+        for _ in 0..MAX_TRACE_PASSES {
+            self.trace_beams();
+            orb_changes.extend(self.update_orbs());
+            let changes = self.update_switches();
+            if changes.is_empty() {
+                break;
+            }
+            switch_changes.extend(changes);
+        }
+        (orb_changes, switch_changes)
+    }
+
+    fn trace_beams(&mut self) {
+        let occupants = self.occupants();
+        let mut inbound: HashMap<ObjectId, Vec<(Direction, LaserType)>> = HashMap::new();
+
+        for _ in 0..MAX_TRACE_PASSES {
+            let mut beams = Vec::new();
+            for (index, object) in self.objects.iter().enumerate() {
+                let id = ObjectId(index);
+                match object.kind {
+                    ObjectKind::LaserSource {
+                        laser_type,
+                        direction,
+                        on: true,
+                    } => beams.push(self.trace_beam(
+                        &occupants,
+                        id,
+                        laser_type,
+                        object.coordinate,
+                        direction,
+                    )),
+                    ObjectKind::Refactor(kind) => {
+                        let received = match inbound.get(&id) {
+                            Some(received) => received,
+                            None => continue,
+                        };
+                        let laser_type = received
+                            .iter()
+                            .fold(received[0].1, |mixed, (_, laser_type)| {
+                                mixed.mix(*laser_type)
+                            });
+                        for direction in kind.directions() {
+                            if received.iter().all(|(side, _)| *side != direction) {
+                                beams.push(self.trace_beam(
+                                    &occupants,
+                                    id,
+                                    laser_type,
+                                    object.coordinate,
+                                    direction,
+                                ));
+                            }
+                        }
+                    }
+                    ObjectKind::Filter {
+                        laser_type: filter_type,
+                        mode,
+                        ..
+                    } => {
+                        let received = match inbound.get(&id) {
+                            Some(received) => received,
+                            None => continue,
+                        };
+                        // Each beam carries on out the far side, and beams entering the same side
+                        // are filtered together.
+                        for side in ALL_DIRECTIONS.iter() {
+                            let entering = received
+                                .iter()
+                                .filter(|(received_side, _)| received_side == side)
+                                .map(|(_, laser_type)| *laser_type)
+                                .reduce(LaserType::mix);
+                            let passed = match (entering, mode) {
+                                (None, _) => None,
+                                (Some(laser_type), FilterMode::Pass) => {
+                                    laser_type.intersection(filter_type)
+                                }
+                                (Some(_), FilterMode::Convert) => Some(filter_type),
+                            };
+                            if let Some(laser_type) = passed {
+                                beams.push(self.trace_beam(
+                                    &occupants,
+                                    id,
+                                    laser_type,
+                                    object.coordinate,
+                                    side.rotated_180(),
+                                ));
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            let next_inbound = self.inbound_lasers(&occupants, &beams);
+            self.beams = beams;
+            if next_inbound == inbound {
+                break;
+            }
+            inbound = next_inbound;
+        }
+    }
+
+    fn trace_beam(
+        &self,
+        occupants: &HashMap<Coordinate, Vec<ObjectId>>,
+        source: ObjectId,
+        laser_type: LaserType,
+        start: Coordinate,
+        direction: Direction,
+    ) -> Beam {
+        let mut heading = direction;
+        let mut bends = Vec::new();
+        // Mirrors can send a beam around in a loop, which ends once it comes back the same way.
+        let mut bounced = HashSet::new();
+        let mut check_coordinate = start + heading.direction();
+        'outer: while self.in_bounds(check_coordinate) {
+            if let Some(ids) = occupants.get(&check_coordinate) {
+                // Opaque objects block the beam, refactors and filters take it in and mirrors turn
+                // it.
+                for id in ids {
+                    match self.object(*id).kind {
+                        ObjectKind::Mirror { kind, .. } => {
+                            if !bounced.insert((check_coordinate, heading)) {
+                                break 'outer;
+                            }
+                            heading = kind.reflect(heading);
+                            bends.push(check_coordinate);
+                        }
+                        ObjectKind::Refactor(_) | ObjectKind::Filter { .. } => break 'outer,
+                        ref kind if kind.is_opaque() => break 'outer,
+                        _ => (),
+                    }
+                }
+            }
+            check_coordinate += heading.direction();
+        }
+
+        Beam {
+            source,
+            direction,
+            laser_type,
+            start,
+            bends,
+            end: check_coordinate,
+            end_direction: heading,
+        }
+    }
+
+    /// The sides of each refactor and filter that beams enter, along with the color entering them.
+    fn inbound_lasers(
+        &self,
+        occupants: &HashMap<Coordinate, Vec<ObjectId>>,
+        beams: &[Beam],
+    ) -> HashMap<ObjectId, Vec<(Direction, LaserType)>> {
+        let mut inbound: HashMap<ObjectId, Vec<(Direction, LaserType)>> = HashMap::new();
+        for beam in beams {
+            let ids = match occupants.get(&beam.end) {
+                Some(ids) => ids,
+                None => continue,
+            };
+            for id in ids {
+                let side = beam.end_direction.rotated_180();
+                let takes_in = match self.object(*id).kind {
+                    ObjectKind::Refactor(kind) => kind.directions().contains(&side),
+                    ObjectKind::Filter { .. } => true,
+                    _ => false,
+                };
+                if takes_in {
+                    let received = inbound.entry(*id).or_default();
+                    if !received.contains(&(side, beam.laser_type)) {
+                        received.push((side, beam.laser_type));
+                    }
+                }
+            }
+        }
+        inbound
+    }
+
+    /// Activates orbs whose rule the beams hitting them satisfy and destroys orbs hit by a color
+    /// the rule rejects. Destroyed orbs stay destroyed.
+    fn update_orbs(&mut self) -> Vec<OrbChange> {
+        let mut changes = Vec::new();
+        for index in 0..self.objects.len() {
+            let coordinate = self.objects[index].coordinate;
+            let (orb_type, rule, state) = match self.objects[index].kind {
+                ObjectKind::Orb {
+                    orb_type,
+                    rule,
+                    state,
+                } => (orb_type, rule, state),
+                _ => continue,
+            };
+            if state == OrbState::Destroyed {
+                continue;
+            }
+
+            let hits = self
+                .beams
+                .iter()
+                .filter(|beam| beam.end == coordinate)
+                .map(|beam| beam.laser_type)
+                .collect::<Vec<_>>();
+            let new_state = judge_orb(orb_type, rule, &hits);
+
+            if new_state != state {
+                self.objects[index].kind = ObjectKind::Orb {
+                    orb_type,
+                    rule,
+                    state: new_state,
+                };
+                changes.push(OrbChange {
+                    orb: ObjectId(index),
+                    from: state,
+                    to: new_state,
+                });
+            }
+        }
+        changes
+    }
+
+    /// Switches each linked gate and laser source on once all of its orbs are lit and plates
+    /// pressed, and off otherwise. A gate stays open while something is in it, gates without
+    /// anything linked stay shut and laser sources without anything linked stay on.
+    fn update_switches(&mut self) -> Vec<SwitchChange> {
+        let occupants = self.occupants();
+        let mut changes = Vec::new();
+        for index in 0..self.objects.len() {
+            let id = ObjectId(index);
+            let switched_on = match self.objects[index].kind.switched_on() {
+                Some(switched_on) => switched_on,
+                None => continue,
+            };
+
+            let triggered = self.triggers.get(&id).and_then(|triggers| {
+                if triggers.is_empty() {
+                    return None;
+                }
+                Some(
+                    triggers
+                        .iter()
+                        .all(|trigger| match self.object(*trigger).kind {
+                            ObjectKind::Orb { state, .. } => state == OrbState::Activated,
+                            ObjectKind::Plate => self.is_pressed(*trigger),
+                            _ => false,
+                        }),
+                )
+            });
+            let new_switched_on = match self.objects[index].kind {
+                ObjectKind::Gate { .. } => {
+                    let occupied = occupants[&self.objects[index].coordinate].len() > 1;
+                    triggered == Some(true) || (switched_on && occupied)
+                }
+                _ => triggered.unwrap_or(true),
+            };
+
+            if new_switched_on != switched_on {
+                self.objects[index].kind.switch(new_switched_on);
+                changes.push(SwitchChange {
+                    object: id,
+                    on: new_switched_on,
+                });
+            }
+        }
+        changes
+    }
+}
+
+/// The state an orb of `orb_type` is left in by beams of the colors in `hits`.
+fn judge_orb(orb_type: LaserType, rule: OrbMatch, hits: &[LaserType]) -> OrbState {
+    if hits.is_empty() {
+        return OrbState::Deactivated;
+    }
+
+    match rule {
+        OrbMatch::Exact if hits.iter().all(|hit| *hit == orb_type) => OrbState::Activated,
+        OrbMatch::Contains if hits.iter().all(|hit| hit.contains(orb_type)) => OrbState::Activated,
+        OrbMatch::Mixed if hits.iter().all(|hit| orb_type.contains(*hit)) => {
+            let mixed = hits[1..].iter().fold(hits[0], |mixed, hit| mixed.mix(*hit));
+            if mixed == orb_type {
+                OrbState::Activated
+            } else {
+                OrbState::Deactivated
+            }
+        }
+        _ => OrbState::Destroyed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(lvl: &str) -> GameState {
+        GameState::new(&LevelDescription::parse(lvl).unwrap())
+    }
+
+    fn coordinates(state: &GameState) -> Vec<Coordinate> {
+        state
+            .objects()
+            .iter()
+            .map(|object| object.coordinate)
+            .collect()
+    }
+
+    #[test]
+    fn push_into_wall_only_turns_jimbo() {
+        let mut state = state("1 | 3\nP | C | W\n");
+        let before = coordinates(&state);
+        let outcome = state.step(Direction::Right);
+        assert!(outcome.moved.is_empty());
+        assert_eq!(coordinates(&state), before);
+        assert_eq!(state.facing(), Direction::Right);
+    }
+
+    #[test]
+    fn pushes_a_chain_of_crates() {
+        let mut state = state("1 | 4\nP | C | C | _\n");
+        let before = coordinates(&state);
+        let outcome = state.step(Direction::Right);
+        assert_eq!(outcome.moved, vec![ObjectId(0), ObjectId(1), ObjectId(2)]);
+        let offset = Direction::Right.direction();
+        let after = before.iter().map(|c| *c + offset).collect::<Vec<_>>();
+        assert_eq!(coordinates(&state), after);
+
+        // The chain now reaches the edge, so it can't go any further.
+        assert!(state.step(Direction::Right).moved.is_empty());
+    }
+
+    #[test]
+    fn lighting_every_orb_completes_the_level() {
+        let mut state = state("2 | 3\nP |LRD| _\n_ | _ |OR\n");
+        assert!(!state.is_complete());
+        let outcome = state.step(Direction::Right);
+        assert_eq!(
+            outcome.orb_changes,
+            vec![OrbChange {
+                orb: ObjectId(2),
+                from: OrbState::Deactivated,
+                to: OrbState::Activated,
+            }]
+        );
+        assert!(outcome.completed);
+        assert!(state.is_complete());
+    }
+
+    #[test]
+    fn revert_restores_the_previous_state() {
+        let mut state = state("2 | 3\nP |LRD| _\n_ | _ |OR\n");
+        let before = state.snapshot();
+        let outcome = state.step(Direction::Right);
+        let after = state.snapshot();
+        assert_ne!(before, after);
+
+        state.revert(&outcome);
+        assert_eq!(state.snapshot(), before);
+        assert!(!state.is_complete());
+
+        state.restore(&after);
+        assert!(state.is_complete());
+    }
+
+    #[test]
+    fn mirrors_bend_beams_and_fixed_ones_stay_put() {
+        let bent = state("2 | 2\nP |OR\nLRR| /\n");
+        assert!(bent.is_complete());
+        assert_eq!(bent.beams()[0].bends, vec![Coordinate { x: 1, y: 0 }]);
+
+        let mut pushable = state("1 | 3\nP | / | _\n");
+        assert_eq!(pushable.step(Direction::Right).moved.len(), 2);
+        let mut fixed = state("1 | 3\nP |F/ | _\n");
+        assert!(fixed.step(Direction::Right).moved.is_empty());
+    }
+
+    #[test]
+    fn orbs_judge_beams_by_their_rule() {
+        use OrbState::*;
+        let (red, green, blue) = (LaserType::RED, LaserType::GREEN, LaserType::BLUE);
+
+        assert_eq!(judge_orb(red, OrbMatch::Exact, &[]), Deactivated);
+        assert_eq!(judge_orb(red, OrbMatch::Exact, &[red, red]), Activated);
+        assert_eq!(
+            judge_orb(red, OrbMatch::Exact, &[LaserType::YELLOW]),
+            Destroyed
+        );
+
+        let yellow = LaserType::YELLOW;
+        assert_eq!(judge_orb(yellow, OrbMatch::Mixed, &[red]), Deactivated);
+        assert_eq!(judge_orb(yellow, OrbMatch::Mixed, &[red, green]), Activated);
+        assert_eq!(judge_orb(yellow, OrbMatch::Mixed, &[red, blue]), Destroyed);
+
+        assert_eq!(
+            judge_orb(red, OrbMatch::Contains, &[LaserType::WHITE]),
+            Activated
+        );
+        assert_eq!(
+            judge_orb(red, OrbMatch::Contains, &[yellow, red]),
+            Activated
+        );
+        assert_eq!(judge_orb(red, OrbMatch::Contains, &[green]), Destroyed);
+    }
+
+    #[test]
+    fn filters_pass_their_share_of_a_beam_or_convert_it() {
+        assert!(state("1 | 4\nP |LWR|TR |OR\n").is_complete());
+
+        let blocked = state("1 | 4\nP |LGR|TR |OR\n");
+        assert!(!blocked.is_complete());
+        assert!(matches!(
+            blocked.object(ObjectId(3)).kind,
+            ObjectKind::Orb {
+                state: OrbState::Deactivated,
+                ..
+            }
+        ));
+
+        assert!(state("1 | 4\nP |LGR|TR=|OR\n").is_complete());
+    }
+
+    #[test]
+    fn gates_open_while_their_orbs_are_lit() {
+        let lvl = "3 | 3\nLRR| G |OR\nP |LRD| _\n_ | _ |OR\nlinks:\na: 1,2 3,3\n";
+        let mut state = state(lvl);
+        let gate = ObjectId(1);
+        assert_eq!(state.object(gate).kind, ObjectKind::Gate { open: false });
+
+        let outcome = state.step(Direction::Right);
+        assert_eq!(
+            outcome.switch_changes,
+            vec![SwitchChange {
+                object: gate,
+                on: true,
+            }]
+        );
+        assert!(state.is_complete());
+
+        state.revert(&outcome);
+        assert_eq!(state.object(gate).kind, ObjectKind::Gate { open: false });
+        assert!(!state.is_complete());
+    }
+
+    #[test]
+    fn plates_switch_laser_sources_while_pressed() {
+        let lvl = "2 | 5\nLRR| _ | _ |OR | _\nP | C | = | _ | _\nlinks:\na: 1,1 2,3\n";
+        let mut state = state(lvl);
+        let (source, plate) = (ObjectId(0), ObjectId(4));
+        assert_eq!(state.object(source).kind.switched_on(), Some(false));
+        assert!(!state.is_pressed(plate));
+
+        let onto_plate = state.step(Direction::Right);
+        assert!(state.is_pressed(plate));
+        assert!(state.is_complete());
+
+        // Jimbo takes the crate's place, which keeps the plate down.
+        let off_plate = state.step(Direction::Right);
+        assert!(state.is_pressed(plate));
+        assert!(off_plate.switch_changes.is_empty());
+
+        state.revert(&off_plate);
+        state.revert(&onto_plate);
+        assert_eq!(state.object(source).kind.switched_on(), Some(false));
+
+        let stacked =
+            GameState::new(&LevelDescription::parse(&lvl.replace("C | =", "_ |=C")).unwrap());
+        assert!(stacked.is_complete());
+    }
+}
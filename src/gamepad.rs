@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::components::Direction;
+
+/// How far a stick has to be pushed to step, and how far back it has to come before it can step
+/// again. The gap keeps a stick resting near the edge from stepping twice.
+const STICK_PRESS: f32 = 0.6;
+const STICK_RELEASE: f32 = 0.3;
+
+/// The same thresholds for analog buttons, matching Bevy's defaults.
+const BUTTON_PRESS: f32 = 0.75;
+const BUTTON_RELEASE: f32 = 0.65;
+
+/// What a gamepad press means, before it's turned into a level command or menu navigation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GamepadAction {
+    Move(Direction),
+    Confirm,
+    Undo,
+    Redo,
+    Restart,
+    Menu,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Stick {
+    Left,
+    /// Some gamepads report the D-pad as a pair of axes rather than buttons.
+    DPad,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct StickState {
+    x: f32,
+    y: f32,
+    /// The direction the stick is pushed in, which has already stepped.
+    held: Option<Direction>,
+}
+
+impl StickState {
+    fn update(&mut self) -> Option<Direction> {
+        if let Some(held) = self.held {
+            let along = match held {
+                Direction::Up => self.y,
+                Direction::Down => -self.y,
+                Direction::Right => self.x,
+                Direction::Left => -self.x,
+            };
+            if along > STICK_RELEASE {
+                return None;
+            }
+            self.held = None;
+        }
+
+        if self.x.abs().max(self.y.abs()) < STICK_PRESS {
+            return None;
+        }
+
+        let direction = if self.x.abs() > self.y.abs() {
+            if self.x > 0.0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            }
+        } else if self.y > 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        };
+        self.held = Some(direction);
+        Some(direction)
+    }
+}
+
+/// Turns `GamepadEvent`s into one action per press, so holding a stick or button down doesn't
+/// repeat. Works from the events alone, so synthetic events drive it the same as a real gamepad.
+#[derive(Debug, Clone, Default)]
+pub struct GamepadInput {
+    pressed: HashSet<GamepadButton>,
+    sticks: HashMap<(Gamepad, Stick), StickState>,
+}
+
+impl GamepadInput {
+    pub fn handle(&mut self, event: &GamepadEvent) -> Option<GamepadAction> {
+        let GamepadEvent(gamepad, event_type) = event;
+        match *event_type {
+            GamepadEventType::Connected => None,
+            GamepadEventType::Disconnected => {
+                self.pressed.retain(|button| button.0 != *gamepad);
+                self.sticks
+                    .retain(|(stick_gamepad, _), _| stick_gamepad != gamepad);
+                None
+            }
+            GamepadEventType::ButtonChanged(button_type, value) => {
+                let button = GamepadButton(*gamepad, button_type);
+                if value <= BUTTON_RELEASE {
+                    self.pressed.remove(&button);
+                    None
+                } else if value >= BUTTON_PRESS && self.pressed.insert(button) {
+                    button_action(button_type)
+                } else {
+                    None
+                }
+            }
+            GamepadEventType::AxisChanged(axis_type, value) => {
+                let (stick, is_x) = match axis_type {
+                    GamepadAxisType::LeftStickX => (Stick::Left, true),
+                    GamepadAxisType::LeftStickY => (Stick::Left, false),
+                    GamepadAxisType::DPadX => (Stick::DPad, true),
+                    GamepadAxisType::DPadY => (Stick::DPad, false),
+                    _ => return None,
+                };
+
+                let state = self.sticks.entry((*gamepad, stick)).or_default();
+                if is_x {
+                    state.x = value;
+                } else {
+                    state.y = value;
+                }
+                state.update().map(GamepadAction::Move)
+            }
+        }
+    }
+}
+
+fn button_action(button_type: GamepadButtonType) -> Option<GamepadAction> {
+    let action = match button_type {
+        GamepadButtonType::DPadUp => GamepadAction::Move(Direction::Up),
+        GamepadButtonType::DPadDown => GamepadAction::Move(Direction::Down),
+        GamepadButtonType::DPadLeft => GamepadAction::Move(Direction::Left),
+        GamepadButtonType::DPadRight => GamepadAction::Move(Direction::Right),
+        GamepadButtonType::South => GamepadAction::Confirm,
+        GamepadButtonType::East | GamepadButtonType::LeftTrigger => GamepadAction::Undo,
+        GamepadButtonType::RightTrigger => GamepadAction::Redo,
+        GamepadButtonType::West => GamepadAction::Restart,
+        GamepadButtonType::North | GamepadButtonType::Start => GamepadAction::Menu,
+        _ => return None,
+    };
+    Some(action)
+}
@@ -0,0 +1,297 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::progress;
+
+/// How many levels can be jumped to with a key, counting from the first.
+pub const LEVEL_HOTKEYS: usize = 10;
+
+/// Something the player does, independent of the keys bound to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Undo,
+    Redo,
+    Restart,
+    Menu,
+    NextLevel,
+    Edit,
+    Controls,
+    SaveReplay,
+    PlayReplay,
+    SlowerPlayback,
+    FasterPlayback,
+    ExportMoves,
+    ImportMoves,
+    Level(usize),
+}
+
+impl Action {
+    /// Every action, in the order the controls screen lists them.
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::MoveUp,
+            Action::MoveDown,
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::Undo,
+            Action::Redo,
+            Action::Restart,
+            Action::Menu,
+            Action::NextLevel,
+            Action::Edit,
+            Action::Controls,
+            Action::SaveReplay,
+            Action::PlayReplay,
+            Action::SlowerPlayback,
+            Action::FasterPlayback,
+            Action::ExportMoves,
+            Action::ImportMoves,
+        ];
+        actions.extend((0..LEVEL_HOTKEYS).map(Action::Level));
+        actions
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Restart => "Restart",
+            Action::Menu => "Menu",
+            Action::NextLevel => "Next level",
+            Action::Edit => "Edit level",
+            Action::Controls => "Controls",
+            Action::SaveReplay => "Save replay",
+            Action::PlayReplay => "Play replay",
+            Action::SlowerPlayback => "Slower playback",
+            Action::FasterPlayback => "Faster playback",
+            Action::ExportMoves => "Export moves",
+            Action::ImportMoves => "Import moves",
+            Action::Level(level) => return write!(f, "Level {}", level + 1),
+        };
+        f.write_str(name)
+    }
+}
+
+/// A key bound to an action. A plain key doesn't fire while Shift is held, so `Z` and `Shift+Z` can
+/// do different things.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Shift(KeyCode),
+}
+
+impl Binding {
+    pub fn just_pressed(&self, keyboard_input: &Input<KeyCode>) -> bool {
+        match *self {
+            Binding::Key(key) => keyboard_input.just_pressed(key) && !shift_pressed(keyboard_input),
+            Binding::Shift(key) => {
+                keyboard_input.just_pressed(key) && shift_pressed(keyboard_input)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Shift(key) => write!(f, "Shift+{:?}", key),
+        }
+    }
+}
+
+pub fn shift_pressed(keyboard_input: &Input<KeyCode>) -> bool {
+    keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindingsError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for KeyBindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+impl std::error::Error for KeyBindingsError {}
+
+/// The keys bound to each action, saved as RON. Actions missing from the file keep their default
+/// keys, so new actions get bound without the player having to reset anything.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBindings {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    /// Where the bindings are saved to, if anywhere.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use Binding::Key;
+
+        let level_keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+            KeyCode::Key0,
+        ];
+
+        let mut bindings = BTreeMap::new();
+        bindings.insert(
+            Action::MoveUp,
+            vec![Key(KeyCode::Up), Key(KeyCode::W), Key(KeyCode::K)],
+        );
+        bindings.insert(
+            Action::MoveDown,
+            vec![Key(KeyCode::Down), Key(KeyCode::S), Key(KeyCode::J)],
+        );
+        bindings.insert(
+            Action::MoveLeft,
+            vec![Key(KeyCode::Left), Key(KeyCode::A), Key(KeyCode::H)],
+        );
+        bindings.insert(
+            Action::MoveRight,
+            vec![Key(KeyCode::Right), Key(KeyCode::D), Key(KeyCode::L)],
+        );
+        bindings.insert(Action::Undo, vec![Key(KeyCode::Z)]);
+        bindings.insert(
+            Action::Redo,
+            vec![Key(KeyCode::Y), Binding::Shift(KeyCode::Z)],
+        );
+        bindings.insert(Action::Restart, vec![Key(KeyCode::R)]);
+        bindings.insert(Action::Menu, vec![Key(KeyCode::Escape)]);
+        bindings.insert(Action::NextLevel, vec![Key(KeyCode::Return)]);
+        bindings.insert(Action::Edit, vec![Key(KeyCode::E)]);
+        bindings.insert(Action::Controls, vec![Key(KeyCode::F1)]);
+        bindings.insert(Action::SaveReplay, vec![Key(KeyCode::F5)]);
+        bindings.insert(Action::PlayReplay, vec![Key(KeyCode::F9)]);
+        bindings.insert(Action::SlowerPlayback, vec![Key(KeyCode::LBracket)]);
+        bindings.insert(Action::FasterPlayback, vec![Key(KeyCode::RBracket)]);
+        bindings.insert(Action::ExportMoves, vec![Key(KeyCode::F6)]);
+        bindings.insert(Action::ImportMoves, vec![Key(KeyCode::F7)]);
+        for (level, key) in level_keys.iter().enumerate() {
+            bindings.insert(Action::Level(level), vec![Key(*key)]);
+        }
+
+        KeyBindings {
+            bindings,
+            path: None,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn default_path() -> Option<PathBuf> {
+        progress::data_dir().map(|dir| dir.join("key_bindings.ron"))
+    }
+
+    /// Loads the bindings at the default path. A missing file means the defaults, while a broken one
+    /// is reported and left alone so saving doesn't clobber it.
+    pub fn load_or_default() -> KeyBindings {
+        let path = match KeyBindings::default_path() {
+            Some(path) => path,
+            None => return KeyBindings::default(),
+        };
+
+        if !path.exists() {
+            return KeyBindings {
+                path: Some(path),
+                ..Default::default()
+            };
+        }
+
+        match KeyBindings::load(&path) {
+            Ok(bindings) => bindings,
+            Err(err) => {
+                eprintln!("{}, using the default key bindings", err);
+                KeyBindings::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<KeyBindings, KeyBindingsError> {
+        let error = |reason: String| KeyBindingsError {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let contents = fs::read_to_string(path)
+            .map_err(|err| error(format!("failed to read key bindings: {}", err)))?;
+        let loaded: KeyBindings = ron::de::from_str(&contents)
+            .map_err(|err| error(format!("failed to parse key bindings: {}", err)))?;
+
+        let mut bindings = KeyBindings::default();
+        bindings.bindings.extend(loaded.bindings);
+        bindings.path = Some(path.to_path_buf());
+        Ok(bindings)
+    }
+
+    /// Writes the bindings, if they have somewhere to go.
+    pub fn save(&self) -> Result<(), KeyBindingsError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let error = |reason: String| KeyBindingsError {
+            path: path.clone(),
+            reason,
+        };
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| error(format!("failed to serialize key bindings: {}", err)))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| error(format!("failed to create data directory: {}", err)))?;
+        }
+        fs::write(path, contents)
+            .map_err(|err| error(format!("failed to write key bindings: {}", err)))
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    /// Binds another key to the action, unless it's already bound to it.
+    pub fn add(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Unbinds every key from the action.
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.insert(action, Vec::new());
+    }
+
+    pub fn just_pressed(&self, action: Action, keyboard_input: &Input<KeyCode>) -> bool {
+        self.get(action)
+            .iter()
+            .any(|binding| binding.just_pressed(keyboard_input))
+    }
+}
@@ -0,0 +1,917 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Coordinate, Direction, LaserType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelParseError {
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub reason: String,
+}
+
+impl LevelParseError {
+    fn new(line: usize, column: usize, token: &str, reason: impl Into<String>) -> Self {
+        LevelParseError {
+            path: None,
+            line,
+            column,
+            token: token.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+}
+
+impl fmt::Display for LevelParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path.display())?;
+        }
+        if self.line > 0 {
+            write!(f, "line {}, column {}: ", self.line, self.column)?;
+        }
+        write!(f, "{}", self.reason)?;
+        if !self.token.is_empty() {
+            write!(f, " (found `{}`)", self.token)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LevelParseError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RefactorKind {
+    /// `R?`: the given direction and the one 90 degrees clockwise.
+    Corner(Direction),
+    /// `S?`: the given direction plus the next two clockwise.
+    Split(Direction),
+    /// `X`: all four directions.
+    Cross,
+}
+
+impl RefactorKind {
+    pub fn directions(&self) -> Vec<Direction> {
+        match *self {
+            Self::Corner(direction) => vec![direction, direction.rotated_90()],
+            Self::Split(direction) => {
+                vec![direction, direction.rotated_90(), direction.rotated_180()]
+            }
+            Self::Cross => vec![
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ],
+        }
+    }
+
+    /// The file name of the texture in `assets/` for this refactor.
+    pub fn material_name(&self) -> String {
+        let mut material_name =
+            self.directions()
+                .iter()
+                .fold(String::from("refactor"), |mut acc, direction| {
+                    acc.push('-');
+                    acc.push_str(direction.material_name());
+                    acc
+                });
+        material_name.push_str(".png");
+        material_name
+    }
+}
+
+/// Which way a mirror leans, as drawn in a `.lvl` file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MirrorKind {
+    /// `/`: turns beams heading right upward and beams heading left downward.
+    Slash,
+    /// `\`: turns beams heading right downward and beams heading left upward.
+    Backslash,
+}
+
+impl MirrorKind {
+    /// The direction a beam heading in `direction` leaves the mirror in.
+    pub fn reflect(&self, direction: Direction) -> Direction {
+        match (self, direction) {
+            (Self::Slash, Direction::Right) | (Self::Backslash, Direction::Left) => Direction::Up,
+            (Self::Slash, Direction::Up) | (Self::Backslash, Direction::Down) => Direction::Right,
+            (Self::Slash, Direction::Left) | (Self::Backslash, Direction::Right) => Direction::Down,
+            (Self::Slash, Direction::Down) | (Self::Backslash, Direction::Up) => Direction::Left,
+        }
+    }
+
+    pub fn flipped(&self) -> MirrorKind {
+        match self {
+            Self::Slash => Self::Backslash,
+            Self::Backslash => Self::Slash,
+        }
+    }
+
+    fn token(&self) -> char {
+        match self {
+            Self::Slash => '/',
+            Self::Backslash => '\\',
+        }
+    }
+}
+
+/// How an orb judges the beams hitting it, written after its color in a `.lvl` file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OrbMatch {
+    /// No suffix: every beam has to be exactly the orb's color.
+    Exact,
+    /// `+`: the beams add up to the orb's color, so a white orb can be lit by red, green and blue
+    /// from different sides. A beam with light the orb lacks still destroys it.
+    Mixed,
+    /// `*`: any beam with the orb's color in it lights it, so a red orb takes yellow and white too.
+    Contains,
+}
+
+impl OrbMatch {
+    /// Cycles through the rules, for the editor.
+    pub fn next(&self) -> OrbMatch {
+        match self {
+            Self::Exact => Self::Mixed,
+            Self::Mixed => Self::Contains,
+            Self::Contains => Self::Exact,
+        }
+    }
+
+    fn token(&self) -> &'static str {
+        match self {
+            Self::Exact => "",
+            Self::Mixed => "+",
+            Self::Contains => "*",
+        }
+    }
+}
+
+/// What a filter does to the beams passing through it, written after its color in a `.lvl` file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FilterMode {
+    /// No suffix: lets through only the light the beam shares with the filter, so red glass turns
+    /// a white beam red and stops a blue one.
+    Pass,
+    /// `=`: turns every beam passing through into the filter's color.
+    Convert,
+}
+
+impl FilterMode {
+    /// Cycles through the modes, for the editor.
+    pub fn next(&self) -> FilterMode {
+        match self {
+            Self::Pass => Self::Convert,
+            Self::Convert => Self::Pass,
+        }
+    }
+
+    fn token(&self) -> &'static str {
+        match self {
+            Self::Pass => "",
+            Self::Convert => "=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Wall,
+    Crate,
+    Jimbo,
+    Orb {
+        orb_type: LaserType,
+        rule: OrbMatch,
+    },
+    Refactor(RefactorKind),
+    LaserSource {
+        laser_type: LaserType,
+        direction: Direction,
+    },
+    /// `/` and `\` can be pushed, while `F/` and `F\` are fixed in place.
+    Mirror {
+        kind: MirrorKind,
+        fixed: bool,
+    },
+    /// `T` followed by a color can be pushed, while `FT` is glass fixed in place.
+    Filter {
+        laser_type: LaserType,
+        mode: FilterMode,
+        fixed: bool,
+    },
+    /// Opens while the orbs and plates it's linked to are lit and pressed.
+    Gate,
+    /// `=` is a pressure plate on its own, and `=` before another token puts that on the plate, as
+    /// in `=C` for a crate starting on one.
+    Plate(Option<Box<Cell>>),
+}
+
+impl Cell {
+    pub fn parse(token: &str) -> Result<Cell, String> {
+        if let Some(top) = token.strip_prefix('=') {
+            if top.is_empty() {
+                return Ok(Cell::Plate(None));
+            }
+            let top = Cell::parse(top)?;
+            if !top.fits_on_plate() {
+                return Err(format!("`{}` can't start on a plate", top.token()));
+            }
+            return Ok(Cell::Plate(Some(Box::new(top))));
+        }
+
+        let cell = match token {
+            "_" => Cell::Empty,
+            "W" => Cell::Wall,
+            "C" => Cell::Crate,
+            "P" => Cell::Jimbo,
+            "X" => Cell::Refactor(RefactorKind::Cross),
+            "G" => Cell::Gate,
+            "/" | "F/" => Cell::Mirror {
+                kind: MirrorKind::Slash,
+                fixed: token.starts_with('F'),
+            },
+            "\\" | "F\\" => Cell::Mirror {
+                kind: MirrorKind::Backslash,
+                fixed: token.starts_with('F'),
+            },
+            x if x.starts_with('T') || x.starts_with("FT") => {
+                let fixed = x.starts_with('F');
+                let mut rest = x.chars().skip(if fixed { 2 } else { 1 });
+                let laser_type = parse_laser_type(rest.next(), "filter color")?;
+                let mode = match rest.next() {
+                    Some('=') => FilterMode::Convert,
+                    _ => FilterMode::Pass,
+                };
+                Cell::Filter {
+                    laser_type,
+                    mode,
+                    fixed,
+                }
+            }
+            x if x.starts_with('R') => {
+                let direction = parse_direction(x.chars().nth(1), "splitter direction")?;
+                Cell::Refactor(RefactorKind::Corner(direction))
+            }
+            x if x.starts_with('S') => {
+                let direction = parse_direction(x.chars().nth(1), "splitter direction")?;
+                Cell::Refactor(RefactorKind::Split(direction))
+            }
+            x if x.starts_with('O') => {
+                let orb_type = parse_laser_type(x.chars().nth(1), "orb color")?;
+                let rule = match x.chars().nth(2) {
+                    Some('+') => OrbMatch::Mixed,
+                    Some('*') => OrbMatch::Contains,
+                    _ => OrbMatch::Exact,
+                };
+                Cell::Orb { orb_type, rule }
+            }
+            x if x.starts_with('L') => {
+                let laser_type = parse_laser_type(x.chars().nth(1), "laser color")?;
+                let direction = parse_direction(x.chars().nth(2), "laser direction")?;
+                Cell::LaserSource {
+                    laser_type,
+                    direction,
+                }
+            }
+            _ => return Err("unrecognized level object".to_string()),
+        };
+
+        if cell.token() != with_current_spelling(token) {
+            return Err("unexpected trailing characters".to_string());
+        }
+
+        Ok(cell)
+    }
+
+    pub fn token(&self) -> String {
+        match self {
+            Cell::Empty => "_".to_string(),
+            Cell::Wall => "W".to_string(),
+            Cell::Crate => "C".to_string(),
+            Cell::Jimbo => "P".to_string(),
+            Cell::Refactor(RefactorKind::Cross) => "X".to_string(),
+            Cell::Gate => "G".to_string(),
+            Cell::Plate(None) => "=".to_string(),
+            Cell::Plate(Some(top)) => format!("={}", top.token()),
+            Cell::Refactor(RefactorKind::Corner(direction)) => {
+                format!("R{}", direction_token(*direction))
+            }
+            Cell::Refactor(RefactorKind::Split(direction)) => {
+                format!("S{}", direction_token(*direction))
+            }
+            Cell::Orb { orb_type, rule } => {
+                format!("O{}{}", laser_type_token(*orb_type), rule.token())
+            }
+            Cell::Mirror { kind, fixed: false } => kind.token().to_string(),
+            Cell::Mirror { kind, fixed: true } => format!("F{}", kind.token()),
+            Cell::Filter {
+                laser_type,
+                mode,
+                fixed,
+            } => format!(
+                "{}T{}{}",
+                if *fixed { "F" } else { "" },
+                laser_type_token(*laser_type),
+                mode.token()
+            ),
+            Cell::LaserSource {
+                laser_type,
+                direction,
+            } => format!(
+                "L{}{}",
+                laser_type_token(*laser_type),
+                direction_token(*direction)
+            ),
+        }
+    }
+
+    /// Gates and laser sources, which are switched by the orbs and plates linked with them.
+    pub fn is_switched(&self) -> bool {
+        matches!(self, Cell::Gate | Cell::LaserSource { .. })
+    }
+
+    /// Orbs and plates, which switch the gates and laser sources linked with them.
+    pub fn is_trigger(&self) -> bool {
+        matches!(self, Cell::Orb { .. } | Cell::Plate(_))
+    }
+
+    /// Jimbo and anything that can be pushed, since those are what can end up on a plate in play.
+    pub fn fits_on_plate(&self) -> bool {
+        matches!(
+            self,
+            Cell::Crate
+                | Cell::Jimbo
+                | Cell::Refactor(_)
+                | Cell::LaserSource { .. }
+                | Cell::Mirror { fixed: false, .. }
+                | Cell::Filter { fixed: false, .. }
+        )
+    }
+
+    /// The cell and whatever is on it, from the floor up.
+    pub fn layers(&self) -> Vec<&Cell> {
+        match self {
+            Cell::Plate(Some(top)) => vec![self, top],
+            _ => vec![self],
+        }
+    }
+}
+
+/// Optional `key: value` lines between the size header and the grid.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// The number of moves a good solution takes.
+    pub par: Option<usize>,
+    pub hint: Option<String>,
+    pub difficulty: Option<String>,
+}
+
+impl LevelInfo {
+    /// The metadata lines present, in the order they are written out.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let fields = vec![
+            ("title", self.title.clone()),
+            ("author", self.author.clone()),
+            ("par", self.par.map(|par| par.to_string())),
+            ("hint", self.hint.clone()),
+            ("difficulty", self.difficulty.clone()),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| (key, value)))
+            .collect()
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let field = match key {
+            "title" => &mut self.title,
+            "author" => &mut self.author,
+            "hint" => &mut self.hint,
+            "difficulty" => &mut self.difficulty,
+            "par" => {
+                if self.par.is_some() {
+                    return Err("duplicate metadata key".to_string());
+                }
+                let par = value
+                    .parse()
+                    .map_err(|_| "expected a number of moves for par".to_string())?;
+                self.par = Some(par);
+                return Ok(());
+            }
+            _ => return Err("unknown metadata key".to_string()),
+        };
+
+        if field.is_some() {
+            return Err("duplicate metadata key".to_string());
+        }
+        *field = Some(value.to_string());
+        Ok(())
+    }
+}
+
+/// Objects that work together, from a `group: row,column ...` line in the `links:` section after the
+/// grid. Every gate in a group opens, and every laser source in it shines, once all of the group's
+/// orbs are lit and plates pressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub group: String,
+    /// Row and column of each cell in the grid, counting from 0 here and from 1 in the file.
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// The link's line in the `links:` section.
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.group)?;
+        for (row, column) in self.cells.iter() {
+            write!(f, " {},{}", row + 1, column + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// A level as written in a `.lvl` file, independent of any spawned entities.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelDescription {
+    pub width: u32,
+    pub height: u32,
+    pub info: LevelInfo,
+    /// Rows in file order, so the first row is the top of the level.
+    pub rows: Vec<Vec<Cell>>,
+    pub links: Vec<Link>,
+}
+
+impl LevelDescription {
+    pub fn load(path: &Path) -> Result<LevelDescription, LevelParseError> {
+        let contents = LevelDescription::read(path)?;
+        LevelDescription::parse(&contents).map_err(|err| err.with_path(path))
+    }
+
+    /// Reads a level file without parsing it.
+    pub fn read(path: &Path) -> Result<String, LevelParseError> {
+        fs::read_to_string(path).map_err(|err| {
+            LevelParseError::new(0, 0, "", format!("failed to read level: {}", err)).with_path(path)
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<LevelDescription, LevelParseError> {
+        let mut lines = contents.lines().enumerate();
+
+        let (_, header) = lines.next().ok_or_else(|| {
+            LevelParseError::new(1, 1, "", "expected level size `height | width`")
+        })?;
+        let mut sizes = split_tokens(header).map(|(column, size)| {
+            size.parse::<u32>().map_err(|_| {
+                LevelParseError::new(1, column, size, "expected a number for the level size")
+            })
+        });
+
+        let height = sizes
+            .next()
+            .unwrap_or_else(|| Err(LevelParseError::new(1, 1, "", "expected level height")))?;
+        let width = sizes.next().unwrap_or_else(|| {
+            Err(LevelParseError::new(
+                1,
+                header.chars().count() + 1,
+                "",
+                "expected level width",
+            ))
+        })?;
+
+        let mut info = LevelInfo::default();
+        let mut rows = Vec::new();
+        let mut links = Vec::new();
+        let mut in_links = false;
+        for (line_index, line) in lines {
+            if in_links {
+                if !line.trim().is_empty() {
+                    links.push(parse_link(line, line_index + 1, &rows)?);
+                }
+                continue;
+            }
+            if !rows.is_empty() && line.trim() == "links:" {
+                in_links = true;
+                continue;
+            }
+
+            // Grid tokens never contain a colon, so these can only be metadata.
+            if rows.is_empty() && line.contains(':') {
+                let mut parts = line.splitn(2, ':');
+                let key = parts.next().unwrap_or_default();
+                let value = parts.next().unwrap_or_default().trim();
+                let column = key.len() - key.trim_start().len() + 1;
+                info.set(key.trim(), value).map_err(|reason| {
+                    LevelParseError::new(line_index + 1, column, key.trim(), reason)
+                })?;
+                continue;
+            }
+
+            let row = split_tokens(line)
+                .map(|(column, token)| {
+                    Cell::parse(token).map_err(|reason| {
+                        LevelParseError::new(line_index + 1, column, token, reason)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push(row);
+        }
+
+        Ok(LevelDescription {
+            width,
+            height,
+            info,
+            rows,
+            links,
+        })
+    }
+
+    pub fn to_lvl_string(&self) -> String {
+        let mut lvl = format!("{} | {}\n", self.height, self.width);
+        for (key, value) in self.info.fields() {
+            lvl.push_str(&format!("{}: {}\n", key, value));
+        }
+        for row in self.rows.iter() {
+            let line = row
+                .iter()
+                .map(|cell| format!("{:^3}", cell.token()))
+                .collect::<Vec<_>>()
+                .join("|");
+            lvl.push_str(line.trim_end());
+            lvl.push('\n');
+        }
+        if !self.links.is_empty() {
+            lvl.push_str("links:\n");
+        }
+        for link in self.links.iter() {
+            lvl.push_str(&format!("{}\n", link));
+        }
+        lvl
+    }
+
+    /// Drops links to cells that can no longer be linked, along with links left empty.
+    pub fn prune_links(&mut self) {
+        let rows = &self.rows;
+        for link in self.links.iter_mut() {
+            link.cells
+                .retain(|(row, column)| is_linkable(rows, *row, *column));
+        }
+        self.links.retain(|link| !link.cells.is_empty());
+    }
+
+    /// Links `trigger` with `switch`, or unlinks it from every link they share, returning whether
+    /// they're linked now. Links with a single cell left do nothing, so they're dropped.
+    pub fn toggle_link(&mut self, switch: (usize, usize), trigger: (usize, usize)) -> bool {
+        let shared = |link: &Link| link.cells.contains(&switch) && link.cells.contains(&trigger);
+        let linked = if self.links.iter().any(shared) {
+            for link in self.links.iter_mut().filter(|link| shared(link)) {
+                link.cells.retain(|cell| *cell != trigger);
+            }
+            false
+        } else {
+            match self
+                .links
+                .iter_mut()
+                .find(|link| link.cells.contains(&switch))
+            {
+                Some(link) => link.cells.push(trigger),
+                None => {
+                    let group = self.unused_group();
+                    self.links.push(Link {
+                        group,
+                        cells: vec![switch, trigger],
+                    });
+                }
+            }
+            true
+        };
+        self.links.retain(|link| link.cells.len() > 1);
+        linked
+    }
+
+    /// The first of `a` to `z` no link is using, then `link27` and on.
+    fn unused_group(&self) -> String {
+        let used = |group: &str| self.links.iter().any(|link| link.group == group);
+        (b'a'..=b'z')
+            .map(|letter| (letter as char).to_string())
+            .chain((27..).map(|number| format!("link{}", number)))
+            .find(|group| !used(group))
+            .unwrap()
+    }
+
+    /// The line of the file a row of the grid was read from, counting from 1.
+    pub fn row_line(&self, row: usize) -> usize {
+        2 + self.info.fields().len() + row
+    }
+
+    /// The game coordinate of a cell, where `y` counts up from the bottom row.
+    pub fn coordinate(&self, row: usize, column: usize) -> Coordinate {
+        Coordinate {
+            x: column as i32,
+            y: self.height as i32 - 1 - row as i32,
+        }
+    }
+
+    /// Every cell in file order along with its game coordinate.
+    pub fn cells(&self) -> impl Iterator<Item = (Coordinate, &Cell)> + '_ {
+        self.rows.iter().enumerate().flat_map(move |(row, cells)| {
+            cells
+                .iter()
+                .enumerate()
+                .map(move |(column, cell)| (self.coordinate(row, column), cell))
+        })
+    }
+}
+
+/// Reads a `group: row,column ...` line, checking each cell against the grid above it.
+fn parse_link(line: &str, line_number: usize, rows: &[Vec<Cell>]) -> Result<Link, LevelParseError> {
+    let mut parts = line.splitn(2, ':');
+    let group = parts.next().unwrap_or_default().trim();
+    let cells = match parts.next() {
+        Some(cells) if !group.is_empty() => cells,
+        _ => {
+            return Err(LevelParseError::new(
+                line_number,
+                1,
+                line.trim(),
+                "expected a link `group: row,column ...`",
+            ))
+        }
+    };
+
+    let mut offset = line.len() - cells.len();
+    let mut link = Link {
+        group: group.to_string(),
+        cells: Vec::new(),
+    };
+    for token in cells.split(' ') {
+        let column = line[..offset].chars().count() + 1;
+        offset += token.len() + 1;
+        if token.is_empty() {
+            continue;
+        }
+
+        let error = |reason: &str| LevelParseError::new(line_number, column, token, reason);
+        let mut numbers = token.splitn(2, ',').map(|number| number.parse::<usize>());
+        let cell = match (numbers.next(), numbers.next()) {
+            (Some(Ok(row)), Some(Ok(column))) if row > 0 && column > 0 => (row - 1, column - 1),
+            _ => return Err(error("expected a cell as `row,column`, counting from 1")),
+        };
+        if !is_linkable(rows, cell.0, cell.1) {
+            return Err(error(
+                "linked cell is not a gate, orb, plate or laser source",
+            ));
+        }
+        link.cells.push(cell);
+    }
+
+    if link.cells.is_empty() {
+        return Err(LevelParseError::new(
+            line_number,
+            line.chars().count() + 1,
+            "",
+            "expected the cells in the link",
+        ));
+    }
+    Ok(link)
+}
+
+/// Gates and laser sources are switched by the orbs and plates linked with them.
+fn is_linkable(rows: &[Vec<Cell>], row: usize, column: usize) -> bool {
+    let cell = match rows.get(row).and_then(|cells| cells.get(column)) {
+        Some(cell) => cell,
+        None => return false,
+    };
+    cell.layers()
+        .into_iter()
+        .any(|layer| layer.is_switched() || layer.is_trigger())
+}
+
+/// Splits a line on `|`, yielding each trimmed token along with its 1-based column.
+fn split_tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    line.split('|').map(move |segment| {
+        let leading = segment.len() - segment.trim_start().len();
+        let column = line[..offset + leading].chars().count() + 1;
+        offset += segment.len() + 1;
+        (column, segment.trim())
+    })
+}
+
+fn parse_direction(direction: Option<char>, what: &str) -> Result<Direction, String> {
+    match direction {
+        Some('U') => Ok(Direction::Up),
+        Some('R') => Ok(Direction::Right),
+        Some('D') => Ok(Direction::Down),
+        Some('L') => Ok(Direction::Left),
+        Some(d) => Err(format!("unrecognized {} `{}`", what, d)),
+        None => Err(format!("expected {}", what)),
+    }
+}
+
+fn direction_token(direction: Direction) -> char {
+    match direction {
+        Direction::Up => 'U',
+        Direction::Right => 'R',
+        Direction::Down => 'D',
+        Direction::Left => 'L',
+    }
+}
+
+/// `P` is what magenta was called before there were other mixes, so older levels still load.
+/// `token` with the old `P` spelling of magenta swapped for the `M` it's written back out as.
+fn with_current_spelling(token: &str) -> String {
+    let color_at = match token.chars().next() {
+        Some('O') | Some('L') | Some('T') => 1,
+        Some('F') if token[1..].starts_with('T') => 2,
+        _ => return token.to_string(),
+    };
+    token
+        .char_indices()
+        .map(|(index, c)| {
+            if index == color_at && c == 'P' {
+                'M'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn parse_laser_type(token: Option<char>, what: &str) -> Result<LaserType, String> {
+    match token {
+        Some('R') => Ok(LaserType::RED),
+        Some('G') => Ok(LaserType::GREEN),
+        Some('B') => Ok(LaserType::BLUE),
+        Some('Y') => Ok(LaserType::YELLOW),
+        Some('M') | Some('P') => Ok(LaserType::MAGENTA),
+        Some('C') => Ok(LaserType::CYAN),
+        Some('W') => Ok(LaserType::WHITE),
+        Some(t) => Err(format!("unrecognized {} `{}`", what, t)),
+        None => Err(format!("expected {}", what)),
+    }
+}
+
+fn laser_type_token(laser_type: LaserType) -> char {
+    match laser_type {
+        LaserType::RED => 'R',
+        LaserType::GREEN => 'G',
+        LaserType::BLUE => 'B',
+        LaserType::YELLOW => 'Y',
+        LaserType::MAGENTA => 'M',
+        LaserType::CYAN => 'C',
+        _ => 'W',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(contents: &str) -> LevelParseError {
+        LevelDescription::parse(contents).expect_err("level should not parse")
+    }
+
+    #[test]
+    fn every_level_round_trips() {
+        let mut checked = 0;
+        for entry in fs::read_dir("levels").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("lvl") {
+                continue;
+            }
+            let level = LevelDescription::load(&path).unwrap_or_else(|err| panic!("{}", err));
+            let written = level.to_lvl_string();
+            assert_eq!(
+                LevelDescription::parse(&written).as_ref(),
+                Ok(&level),
+                "{} changed after being written out as:\n{}",
+                path.display(),
+                written
+            );
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn aliases_plates_and_links_round_trip() {
+        let level = LevelDescription::parse(
+            "3 | 4\n\
+             title: Round Trip\n\
+             par: 3\n\
+             P |=C | = |LPU\n\
+             _ | G |OR+|TG=\n\
+             W | W |=LRR| _\n\
+             links:\n\
+             a: 2,2 1,3\n\
+             b: 3,3 2,3\n",
+        )
+        .unwrap();
+        assert_eq!(level.rows[0][1], Cell::Plate(Some(Box::new(Cell::Crate))),);
+        assert_eq!(
+            level.rows[0][3],
+            Cell::LaserSource {
+                laser_type: LaserType::MAGENTA,
+                direction: Direction::Up,
+            }
+        );
+        assert_eq!(level.links[1].cells, vec![(2, 2), (1, 2)]);
+
+        let written = level.to_lvl_string();
+        assert!(written.contains("LMU"));
+        assert_eq!(LevelDescription::parse(&written), Ok(level));
+    }
+
+    #[test]
+    fn toggled_links_round_trip() {
+        let mut level = LevelDescription::parse("2 | 3\nP | G |OR\n = |LRU|OB\n").unwrap();
+        assert!(level.toggle_link((0, 1), (0, 2)));
+        assert!(level.toggle_link((0, 1), (1, 0)));
+        assert!(level.toggle_link((1, 1), (1, 2)));
+        assert_eq!(level.links.len(), 2);
+        assert_eq!(level.links[0].to_string(), "a: 1,2 1,3 2,1");
+        assert_eq!(level.links[1].to_string(), "b: 2,2 2,3");
+
+        assert!(!level.toggle_link((1, 1), (1, 2)));
+        assert!(!level.toggle_link((0, 1), (0, 2)));
+        assert_eq!(level.links.len(), 1);
+        assert_eq!(level.links[0].to_string(), "a: 1,2 2,1");
+
+        let written = level.to_lvl_string();
+        assert!(written.ends_with("links:\na: 1,2 2,1\n"));
+        assert_eq!(LevelDescription::parse(&written), Ok(level));
+    }
+
+    #[test]
+    fn malformed_header() {
+        assert_eq!(
+            parse_error(""),
+            LevelParseError::new(1, 1, "", "expected level size `height | width`")
+        );
+        assert_eq!(
+            parse_error("x | 3\n"),
+            LevelParseError::new(1, 1, "x", "expected a number for the level size")
+        );
+        assert_eq!(
+            parse_error("1 | 1\nmood: grim\nP\n"),
+            LevelParseError::new(2, 1, "mood", "unknown metadata key")
+        );
+    }
+
+    #[test]
+    fn malformed_cells() {
+        assert_eq!(
+            parse_error("1 | 2\nP | Q\n"),
+            LevelParseError::new(2, 5, "Q", "unrecognized level object")
+        );
+        assert_eq!(
+            parse_error("1 | 2\nP |=W\n"),
+            LevelParseError::new(2, 4, "=W", "`W` can't start on a plate")
+        );
+        assert_eq!(
+            parse_error("1 | 2\nP |LRRX\n"),
+            LevelParseError::new(2, 4, "LRRX", "unexpected trailing characters")
+        );
+        assert_eq!(
+            parse_error("1 | 2\nP |OP+x\n"),
+            LevelParseError::new(2, 4, "OP+x", "unexpected trailing characters")
+        );
+    }
+
+    #[test]
+    fn malformed_links() {
+        assert_eq!(
+            parse_error("1 | 2\nP | G\nlinks:\na: 1,1\n"),
+            LevelParseError::new(
+                4,
+                4,
+                "1,1",
+                "linked cell is not a gate, orb, plate or laser source"
+            )
+        );
+        assert_eq!(
+            parse_error("1 | 2\nP | G\nlinks:\na: 0,2\n"),
+            LevelParseError::new(
+                4,
+                4,
+                "0,2",
+                "expected a cell as `row,column`, counting from 1"
+            )
+        );
+        assert_eq!(
+            parse_error("1 | 2\nP | G\nlinks:\na:\n"),
+            LevelParseError::new(4, 3, "", "expected the cells in the link")
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::level::LevelDescription;
+
+/// The directory scanned for pack manifests at startup.
+pub const PACKS_DIR: &str = "levels";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelPackError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for LevelPackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+impl std::error::Error for LevelPackError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelEntry {
+    /// Relative to the manifest in the file, resolved against its directory once loaded.
+    pub file: PathBuf,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// A `.ron` manifest listing an ordered set of levels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelPack {
+    pub name: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Packs are listed by `order`, then by name.
+    #[serde(default)]
+    pub order: i32,
+    pub levels: Vec<LevelEntry>,
+}
+
+impl LevelPack {
+    pub fn load(path: &Path) -> Result<LevelPack, LevelPackError> {
+        let error = |reason: String| LevelPackError {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let contents = fs::read_to_string(path)
+            .map_err(|err| error(format!("failed to read level pack: {}", err)))?;
+        let mut pack: LevelPack = ron::de::from_str(&contents)
+            .map_err(|err| error(format!("failed to parse level pack: {}", err)))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for level in pack.levels.iter_mut() {
+            level.file = dir.join(&level.file);
+        }
+        pack.fill_titles();
+
+        Ok(pack)
+    }
+
+    /// A pack of level files that has no manifest.
+    pub fn from_files(name: String, files: Vec<PathBuf>) -> LevelPack {
+        let mut pack = LevelPack {
+            name,
+            author: None,
+            description: None,
+            order: 0,
+            levels: files
+                .into_iter()
+                .map(|file| LevelEntry { file, title: None })
+                .collect(),
+        };
+        pack.fill_titles();
+        pack
+    }
+
+    /// Levels without a title in the manifest use the one in their file, if it has one.
+    fn fill_titles(&mut self) {
+        for level in self.levels.iter_mut() {
+            if level.title.is_none() {
+                level.title = LevelDescription::load(&level.file)
+                    .ok()
+                    .and_then(|description| description.info.title);
+            }
+        }
+    }
+}
+
+/// Every loaded pack. Levels are addressed by their index across all packs in order, so the level
+/// after the last one in a pack is the first one in the next pack.
+#[derive(Debug, Clone, Default)]
+pub struct LevelPacks {
+    pub packs: Vec<LevelPack>,
+}
+
+impl LevelPacks {
+    /// Loads every `.ron` manifest in `dir`. A broken manifest is reported and skipped so the other
+    /// packs stay playable. A directory without any manifests is loaded as a single pack of its
+    /// `.lvl` files in name order.
+    pub fn load_dir(dir: &Path) -> LevelPacks {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("Failed to read level packs in {}: {}", dir.display(), err);
+                return LevelPacks::default();
+            }
+        };
+
+        let paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>();
+        let has_extension =
+            |path: &PathBuf, extension: &str| path.extension() == Some(OsStr::new(extension));
+
+        if !paths.iter().any(|path| has_extension(path, "ron")) {
+            let mut files = paths
+                .into_iter()
+                .filter(|path| has_extension(path, "lvl"))
+                .collect::<Vec<_>>();
+            if files.is_empty() {
+                return LevelPacks::default();
+            }
+            files.sort();
+
+            let name = dir.file_name().map_or_else(
+                || dir.display().to_string(),
+                |name| name.to_string_lossy().into(),
+            );
+            return LevelPacks {
+                packs: vec![LevelPack::from_files(name, files)],
+            };
+        }
+
+        let mut packs = paths
+            .into_iter()
+            .filter(|path| has_extension(path, "ron"))
+            .filter_map(|path| match LevelPack::load(&path) {
+                Ok(pack) => Some(pack),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        packs.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+
+        LevelPacks { packs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.packs.iter().map(|pack| pack.levels.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every level along with the index of the pack it belongs to.
+    pub fn levels(&self) -> impl Iterator<Item = (usize, &LevelEntry)> {
+        self.packs
+            .iter()
+            .enumerate()
+            .flat_map(|(pack_index, pack)| pack.levels.iter().map(move |level| (pack_index, level)))
+    }
+
+    pub fn level(&self, index: usize) -> Option<&LevelEntry> {
+        self.levels().nth(index).map(|(_, level)| level)
+    }
+}
@@ -9,8 +9,28 @@ pub use components::{Direction, Size};
 pub mod events;
 pub use events::*;
 
+pub mod game_state;
+pub use game_state::{GameState, ObjectId};
+
+pub mod gamepad;
+
+pub mod key_bindings;
+pub use key_bindings::KeyBindings;
+
+pub mod level;
+
+pub mod level_pack;
+pub use level_pack::LevelPacks;
+
+pub mod lurd;
+
 pub mod map;
 
+pub mod progress;
+pub use progress::Progress;
+
+pub mod replay;
+
 pub mod resources;
 pub use resources::*;
 
@@ -19,16 +39,3 @@ pub use startup_systems::StartupSystemPlugin;
 
 pub mod system_stages;
 pub use system_stages::SystemStagesPlugin;
-
-pub static LEVELS: [&str; 10] = [
-    "levels/1.lvl",
-    "levels/2.lvl",
-    "levels/3.lvl",
-    "levels/multi-color.lvl",
-    "levels/playground-option-2.lvl",
-    "levels/playground.lvl",
-    "levels/scene-test.lvl",
-    "levels/standoff.lvl",
-    "levels/trapped-orb.lvl",
-    "levels/nand.lvl",
-];
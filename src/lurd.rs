@@ -0,0 +1,131 @@
+//! LURD notation, the way push-puzzle solutions are usually shared: one letter per move in the
+//! direction taken, uppercase when the move pushed something.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::game_state::StepOutcome;
+use crate::progress;
+use crate::replay;
+use crate::Direction;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LurdError {
+    /// Counting characters from 1.
+    pub position: usize,
+    pub found: char,
+}
+
+impl fmt::Display for LurdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "character {}: expected one of `lurdLURD` (found `{}`)",
+            self.position, self.found
+        )
+    }
+}
+
+impl std::error::Error for LurdError {}
+
+/// The letter for a step, or `None` if Jimbo was blocked and didn't move.
+pub fn step_char(outcome: &StepOutcome) -> Option<char> {
+    if outcome.moved.is_empty() {
+        return None;
+    }
+
+    let letter = match outcome.direction {
+        Direction::Up => 'u',
+        Direction::Right => 'r',
+        Direction::Down => 'd',
+        Direction::Left => 'l',
+    };
+
+    if outcome.pushed() {
+        Some(letter.to_ascii_uppercase())
+    } else {
+        Some(letter)
+    }
+}
+
+/// The direction of every move, skipping whitespace so long solutions can be wrapped. Whether a
+/// move pushes is decided by the level, so the case of each letter is not checked.
+pub fn parse(lurd: &str) -> Result<Vec<Direction>, LurdError> {
+    lurd.chars()
+        .enumerate()
+        .filter(|(_, c)| !c.is_whitespace())
+        .map(|(index, c)| match c.to_ascii_lowercase() {
+            'u' => Ok(Direction::Up),
+            'r' => Ok(Direction::Right),
+            'd' => Ok(Direction::Down),
+            'l' => Ok(Direction::Left),
+            _ => Err(LurdError {
+                position: index + 1,
+                found: c,
+            }),
+        })
+        .collect()
+}
+
+/// Where the solution for a level is exported to and imported from:
+/// `solutions/<level name>-<level hash>.lurd` in the data directory.
+pub fn default_path(level: &Path, level_hash: u64) -> Option<PathBuf> {
+    let name = replay::saved_file_stem(level, level_hash)?;
+    progress::data_dir().map(|dir| dir.join("solutions").join(name).with_extension("lurd"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::GameState;
+    use crate::level::LevelDescription;
+
+    #[test]
+    fn parses_either_case_and_skips_whitespace() {
+        use Direction::*;
+        assert_eq!(parse("uRdL"), Ok(vec![Up, Right, Down, Left]));
+        assert_eq!(parse(" ul\n\tDR \n"), Ok(vec![Up, Left, Down, Right]));
+        assert_eq!(parse(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn rejects_other_characters() {
+        assert_eq!(
+            parse("uu x"),
+            Err(LurdError {
+                position: 4,
+                found: 'x',
+            })
+        );
+        // Run lengths aren't part of the notation here.
+        assert_eq!(
+            parse("3r"),
+            Err(LurdError {
+                position: 1,
+                found: '3',
+            })
+        );
+    }
+
+    #[test]
+    fn exported_solutions_parse_back() {
+        for (file, solution) in [
+            ("levels/1.lvl", "RRRRuRRDu"),
+            ("levels/mirrors.lvl", "uLLdlU"),
+            ("levels/filters.lvl", "uUrrUd"),
+            ("levels/gates.lvl", "rUdldddrruLL"),
+            ("levels/plates.lvl", "uuruulDDDldRuurrrddrUl"),
+        ]
+        .iter()
+        {
+            let mut state = GameState::new(&LevelDescription::load(file.as_ref()).unwrap());
+            let exported = parse(solution)
+                .unwrap()
+                .into_iter()
+                .filter_map(|direction| step_char(&state.step(direction)))
+                .collect::<String>();
+            assert_eq!(&exported, solution, "{}", file);
+            assert!(state.is_complete(), "{}", file);
+        }
+    }
+}
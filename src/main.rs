@@ -1,15 +1,39 @@
 use bevy::prelude::*;
 use bevy::render::pass::ClearColor;
+use std::path::PathBuf;
+use std::process;
 
+use game::level_pack::LevelPack;
 use game::*;
 
+const DEFAULT_WIDTH: f32 = 1500.0;
+const DEFAULT_HEIGHT: f32 = 700.0;
+
+/// The smallest window `--window-size` accepts, which still fits the menus.
+const MIN_WIDTH: f32 = 320.0;
+const MIN_HEIGHT: f32 = 240.0;
+
+struct Options {
+    packs: Option<LevelPacks>,
+    start_level: Option<usize>,
+    width: f32,
+    height: f32,
+}
+
 fn main() {
-    App::build()
+    let options = parse_args();
+
+    let mut app = App::build();
+    if let Some(packs) = options.packs {
+        app.add_resource(packs);
+    }
+
+    app.add_resource(StartLevel(options.start_level))
         .add_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
         .add_resource(WindowDescriptor {
             title: "Game!".to_string(),
-            width: 1500.0,
-            height: 700.0,
+            width: options.width,
+            height: options.height,
             ..Default::default()
         })
         .add_plugins(DefaultPlugins)
@@ -19,3 +43,94 @@ fn main() {
         .add_plugin(SystemStagesPlugin)
         .run();
 }
+
+fn parse_args() -> Options {
+    let mut files = Vec::new();
+    let mut pack_dir = None;
+    let mut start_level = None;
+    let mut width = DEFAULT_WIDTH;
+    let mut height = DEFAULT_HEIGHT;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pack" => {
+                let dir = args
+                    .next()
+                    .unwrap_or_else(|| usage("--pack needs a directory"));
+                pack_dir = Some(PathBuf::from(dir));
+            }
+            "--level" => {
+                let level = args
+                    .next()
+                    .and_then(|level| level.parse::<usize>().ok())
+                    .filter(|level| *level > 0)
+                    .unwrap_or_else(|| usage("--level needs a level number, counting from 1"));
+                start_level = Some(level - 1);
+            }
+            "--window-size" => {
+                let size = args.next().unwrap_or_default();
+                let mut dimensions = size
+                    .splitn(2, 'x')
+                    .map(|dimension| dimension.parse::<f32>().ok().filter(|d| d.is_finite()));
+                match (dimensions.next().flatten(), dimensions.next().flatten()) {
+                    (Some(w), Some(h)) if w >= MIN_WIDTH && h >= MIN_HEIGHT => {
+                        width = w;
+                        height = h;
+                    }
+                    _ => usage(&format!(
+                        "--window-size needs a size like 1500x700, at least {}x{}",
+                        MIN_WIDTH, MIN_HEIGHT
+                    )),
+                }
+            }
+            _ if arg.starts_with('-') => usage(&format!("unknown option `{}`", arg)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    let packs = match (pack_dir, files.is_empty()) {
+        (Some(_), false) => usage("give either level files or --pack, not both"),
+        (Some(dir), true) => {
+            let packs = LevelPacks::load_dir(&dir);
+            if packs.is_empty() {
+                usage(&format!("no levels found in {}", dir.display()));
+            }
+            Some(packs)
+        }
+        (None, false) => {
+            // Straight into the first level given unless told otherwise.
+            start_level = start_level.or(Some(0));
+            Some(LevelPacks {
+                packs: vec![LevelPack::from_files("Command Line".to_string(), files)],
+            })
+        }
+        (None, true) => None,
+    };
+
+    if let (Some(level), Some(packs)) = (start_level, &packs) {
+        if level >= packs.len() {
+            usage(&format!(
+                "--level {} is past the last level ({})",
+                level + 1,
+                packs.len()
+            ));
+        }
+    }
+
+    Options {
+        packs,
+        start_level,
+        width,
+        height,
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("error: {}", error);
+    }
+    eprintln!("usage: game [--pack DIR] [--level N] [--window-size WIDTHxHEIGHT] [LEVEL.lvl...]");
+    eprintln!("Level files open straight into the first one; --level opens the Nth level instead.");
+    process::exit(2);
+}
@@ -1,126 +1,130 @@
+use bevy::ecs::SystemParam;
 use bevy::prelude::*;
-use std::collections::HashSet;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufReader;
 use std::path::Path as FilePath;
 
+use crate::game_state::ObjectKind;
+use crate::level::{LevelDescription, LevelInfo, MirrorKind, RefactorKind};
+use crate::replay::{Playback, Replay, ReplayRecorder};
+use crate::system_stages::walk::Walk;
 use crate::*;
 
-pub fn load_level(
-    path: &FilePath,
-    mut commands: &mut Commands,
-    materials: &Res<Materials>,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    level_size: &mut ResMut<LevelSize>,
-) {
-    let level_file =
-        File::open(path).unwrap_or_else(|err| panic!("Failed to open level: {:?}\n{}", path, err));
-    let reader = BufReader::new(level_file);
+pub use crate::level::LevelParseError;
 
-    let mut lines = reader.lines().enumerate();
+/// The resources a level is spawned into.
+#[derive(SystemParam)]
+pub struct LevelResources<'a> {
+    pub materials: Res<'a, Materials>,
+    pub meshes: ResMut<'a, Assets<Mesh>>,
+    pub level_size: ResMut<'a, LevelSize>,
+    pub game_state: ResMut<'a, GameState>,
+    pub level_info: ResMut<'a, LevelInfo>,
+    pub level: ResMut<'a, LevelDescription>,
+    pub turn_counter: ResMut<'a, TurnCounter>,
+    pub move_log: ResMut<'a, MoveLog>,
+    pub undo_buffer: ResMut<'a, UndoBuffer>,
+    pub redo_buffer: ResMut<'a, RedoBuffer>,
+    pub recorder: ResMut<'a, ReplayRecorder>,
+    pub playback: ResMut<'a, Playback>,
+    pub walk: ResMut<'a, Walk>,
+}
 
-    let (_, sizes) = lines.next().expect("expected level size");
-    let sizes = sizes.expect("expected level sizes");
-    let mut sizes = sizes
-        .split('|')
-        .map(|size| size.trim())
-        .map(|size| size.parse::<u32>().expect("expected a number for the size"));
+pub fn load_level(path: &FilePath, commands: &mut Commands, resources: &mut LevelResources) {
+    try_load_level(path, commands, resources)
+        .unwrap_or_else(|err| panic!("Failed to load level: {}", err));
+}
 
-    let height = sizes.next().expect("expected level height");
-    let width = sizes.next().expect("expected level width");
-    level_size.width = width;
-    level_size.height = height;
+/// Parses the whole level before spawning anything so a malformed file leaves the world untouched.
+pub fn try_load_level(
+    path: &FilePath,
+    commands: &mut Commands,
+    resources: &mut LevelResources,
+) -> Result<LevelDescription, LevelParseError> {
+    let contents = LevelDescription::read(path)?;
+    let level = LevelDescription::parse(&contents).map_err(|err| err.with_path(path))?;
+    spawn_level(&level, commands, resources);
+    resources.recorder.0 = Replay::new(path, &contents);
+    Ok(level)
+}
 
-    for (y, line) in lines {
-        let y = y as i32;
-        let line = line.unwrap_or_else(|err| panic!("Error loading level: {:?}\n{}", path, err));
-        for (x, object) in line.split('|').enumerate() {
-            let x = x as i32;
-            let coord = Coordinate {
-                x,
-                y: ((height as i32) - y),
-            };
-            spawn_tile(&mut commands, &materials, coord);
-            let object = object.trim();
+pub fn spawn_level(
+    level: &LevelDescription,
+    commands: &mut Commands,
+    resources: &mut LevelResources,
+) {
+    let state = GameState::new(level);
+    let materials = &resources.materials;
+    let meshes = &mut resources.meshes;
+    resources.level_size.width = level.width;
+    resources.level_size.height = level.height;
+    *resources.level_info = level.info.clone();
 
-            match object {
-                "W" => spawn_wall(commands, materials, coord),
-                "C" => spawn_crate(commands, materials, coord),
-                "P" => spawn_jimbo(commands, materials, coord),
-                "X" => {
-                    let directions = vec![
-                        crate::Direction::Up,
-                        crate::Direction::Right,
-                        crate::Direction::Down,
-                        crate::Direction::Left,
-                    ];
-                    spawn_refactor(commands, materials, meshes, directions, coord);
-                }
-                x if x.starts_with('R') => {
-                    let mut chars = x.chars().skip(1);
-                    let direction = match chars.next().expect("expected splitter direction") {
-                        'U' => crate::Direction::Up,
-                        'R' => crate::Direction::Right,
-                        'D' => crate::Direction::Down,
-                        'L' => crate::Direction::Left,
-                        d => panic!("Unrecognized direction: {}", d),
-                    };
-                    let directions = vec![direction, direction.rotated_90()];
-                    spawn_refactor(commands, materials, meshes, directions, coord);
-                }
-                x if x.starts_with('S') => {
-                    let mut chars = x.chars().skip(1);
-                    let direction = match chars.next().expect("expected splitter direction") {
-                        'U' => crate::Direction::Up,
-                        'R' => crate::Direction::Right,
-                        'D' => crate::Direction::Down,
-                        'L' => crate::Direction::Left,
-                        d => panic!("Unrecognized direction: {}", d),
-                    };
-                    let directions =
-                        vec![direction, direction.rotated_90(), direction.rotated_180()];
-                    spawn_refactor(commands, materials, meshes, directions, coord);
-                }
-                x if x.starts_with('O') => {
-                    let mut chars = x.chars().skip(1);
-                    let laser_type = match chars.next().expect("expected laser type") {
-                        'R' => LaserType::Red,
-                        'B' => LaserType::Blue,
-                        'P' => LaserType::Purple,
-                        t => panic!("Unrecognized laser type: {}", t),
-                    };
-                    spawn_orb(commands, materials, laser_type, coord);
-                }
-                x if x.starts_with('L') => {
-                    let mut chars = x.chars().skip(1);
-                    let laser_type = match chars.next().expect("expected laser type") {
-                        'R' => LaserType::Red,
-                        'B' => LaserType::Blue,
-                        t => panic!("Unrecognized laser type: {}", t),
-                    };
+    for (coord, _) in level.cells() {
+        spawn_tile(commands, materials, coord);
+    }
 
-                    let laser_direction = match chars.next().expect("expected laser direction") {
-                        'U' => crate::Direction::Up,
-                        'R' => crate::Direction::Right,
-                        'D' => crate::Direction::Down,
-                        'L' => crate::Direction::Left,
-                        d => panic!("Unrecognized laser direction: {:?}", d),
-                    };
-                    spawn_laser_source(
-                        commands,
-                        materials,
-                        meshes,
-                        laser_type,
-                        laser_direction,
-                        coord,
-                    );
-                }
-                "_" => (),
-                _ => panic!("Unrecognized level object: {}", object),
+    for (index, object) in state.objects().iter().enumerate() {
+        let id = ObjectId(index);
+        let coord = object.coordinate;
+        match object.kind {
+            ObjectKind::Wall => spawn_wall(commands, materials, id, coord),
+            ObjectKind::Crate => spawn_crate(commands, materials, id, coord),
+            ObjectKind::Jimbo => spawn_jimbo(commands, materials, id, coord),
+            ObjectKind::Refactor(kind) => {
+                spawn_refactor(commands, materials, meshes, kind, id, coord)
+            }
+            ObjectKind::Orb {
+                orb_type, state, ..
+            } => spawn_orb(commands, materials, orb_type, state, id, coord),
+            ObjectKind::LaserSource {
+                laser_type,
+                direction,
+                on,
+            } => {
+                let source = LaserSource {
+                    direction,
+                    laser_type,
+                    on,
+                };
+                spawn_laser_source(commands, materials, meshes, source, id, coord)
+            }
+            ObjectKind::Mirror { kind, fixed } => {
+                spawn_mirror(commands, materials, kind, fixed, id, coord)
+            }
+            ObjectKind::Filter {
+                laser_type,
+                mode,
+                fixed,
+            } => {
+                let material = materials.filter(laser_type, mode, fixed);
+                spawn_filter(commands, materials, meshes, material, fixed, id, coord)
+            }
+            ObjectKind::Gate { open } => spawn_gate(commands, materials, open, id, coord),
+            ObjectKind::Plate => {
+                let pressed = state.is_pressed(id);
+                spawn_plate(commands, materials, pressed, id, coord)
             }
         }
     }
+
+    *resources.game_state = state;
+    *resources.level = level.clone();
+    clear_turns(resources);
+}
+
+/// Puts the level back how it was loaded and forgets every turn taken, without respawning anything.
+pub fn reset_level(resources: &mut LevelResources) {
+    *resources.game_state = GameState::new(&resources.level);
+    clear_turns(resources);
+}
+
+fn clear_turns(resources: &mut LevelResources) {
+    resources.turn_counter.0 = 0;
+    resources.move_log.clear();
+    resources.undo_buffer.0.clear();
+    resources.redo_buffer.0.clear();
+    resources.recorder.0.commands.clear();
+    resources.playback.stop();
+    resources.walk.0.stop();
 }
 
 pub fn spawn_tile(commands: &mut Commands, materials: &Res<Materials>, coordinate: Coordinate) {
@@ -138,7 +142,12 @@ pub fn spawn_tile(commands: &mut Commands, materials: &Res<Materials>, coordinat
         .with(coordinate);
 }
 
-pub fn spawn_crate(commands: &mut Commands, materials: &Res<Materials>, coordinate: Coordinate) {
+pub fn spawn_crate(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    id: ObjectId,
+    coordinate: Coordinate,
+) {
     commands
         .spawn(SpriteBundle {
             material: materials.crate_material.clone(),
@@ -151,6 +160,7 @@ pub fn spawn_crate(commands: &mut Commands, materials: &Res<Materials>, coordina
         .with(LevelObject)
         .with(Crate)
         .with(Movable(true))
+        .with(id)
         .with(coordinate)
         .with(Opaque)
         .with(crate::Size {
@@ -159,7 +169,12 @@ pub fn spawn_crate(commands: &mut Commands, materials: &Res<Materials>, coordina
         });
 }
 
-pub fn spawn_wall(commands: &mut Commands, materials: &Res<Materials>, coordinate: Coordinate) {
+pub fn spawn_wall(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    id: ObjectId,
+    coordinate: Coordinate,
+) {
     commands
         .spawn(SpriteBundle {
             material: materials.wall.clone(),
@@ -172,6 +187,7 @@ pub fn spawn_wall(commands: &mut Commands, materials: &Res<Materials>, coordinat
         .with(LevelObject)
         .with(Wall)
         .with(Movable(false))
+        .with(id)
         .with(coordinate)
         .with(Opaque)
         .with(crate::Size {
@@ -184,26 +200,13 @@ pub fn spawn_laser_source(
     commands: &mut Commands,
     materials: &Res<Materials>,
     meshes: &mut ResMut<Assets<Mesh>>,
-    laser_type: LaserType,
-    direction: crate::Direction,
+    source: LaserSource,
+    id: ObjectId,
     coordinate: Coordinate,
 ) {
-    let material = match (laser_type, direction) {
-        (LaserType::Red, crate::Direction::Right) => materials.laser_source_red_right.clone(),
-        (LaserType::Red, crate::Direction::Down) => materials.laser_source_red_down.clone(),
-        (LaserType::Red, crate::Direction::Left) => materials.laser_source_red_left.clone(),
-        (LaserType::Red, crate::Direction::Up) => materials.laser_source_red_up.clone(),
-        (LaserType::Blue, crate::Direction::Right) => materials.laser_source_blue_right.clone(),
-        (LaserType::Blue, crate::Direction::Down) => materials.laser_source_blue_down.clone(),
-        (LaserType::Blue, crate::Direction::Left) => materials.laser_source_blue_left.clone(),
-        (LaserType::Blue, crate::Direction::Up) => materials.laser_source_blue_up.clone(),
-        (LaserType::Purple, crate::Direction::Up) => materials.laser_source_purple_up.clone(),
-        (LaserType::Purple, crate::Direction::Right) => materials.laser_source_purple_right.clone(),
-        (LaserType::Purple, crate::Direction::Down) => materials.laser_source_purple_down.clone(),
-        (LaserType::Purple, crate::Direction::Left) => materials.laser_source_purple_left.clone(),
-    };
+    let material = materials.laser_source(source.laser_type, source.direction, source.on);
 
-    let source = commands
+    let entity = commands
         .spawn(SpriteBundle {
             material,
             sprite: Sprite {
@@ -213,11 +216,9 @@ pub fn spawn_laser_source(
             ..Default::default()
         })
         .with(LevelObject)
-        .with(LaserSource {
-            direction,
-            laser_type,
-        })
+        .with(source)
         .with(Movable(true))
+        .with(id)
         .with(coordinate)
         .with(Opaque)
         .with(crate::Size {
@@ -226,7 +227,15 @@ pub fn spawn_laser_source(
         })
         .current_entity()
         .expect("should've had laser source");
-    spawn_laser(commands, materials, meshes, laser_type, coordinate, source);
+    spawn_laser(
+        commands,
+        materials,
+        meshes,
+        source.laser_type,
+        coordinate,
+        entity,
+        source.direction,
+    );
 }
 
 pub fn spawn_laser(
@@ -236,12 +245,9 @@ pub fn spawn_laser(
     laser_type: LaserType,
     end: Coordinate,
     source: Entity,
+    direction: crate::Direction,
 ) -> Entity {
-    let material = match laser_type {
-        LaserType::Red => materials.laser_red.clone(),
-        LaserType::Blue => materials.laser_blue.clone(),
-        LaserType::Purple => materials.laser_purple.clone(),
-    };
+    let material = materials.laser(laser_type);
     let mesh = system_stages::laser::default_mesh();
     let mesh = meshes.add(mesh);
     commands
@@ -257,6 +263,7 @@ pub fn spawn_laser(
         .with(LevelObject)
         .with(Laser {
             source,
+            direction,
             laser_type,
             end,
         })
@@ -264,7 +271,12 @@ pub fn spawn_laser(
         .unwrap()
 }
 
-pub fn spawn_jimbo(commands: &mut Commands, materials: &Res<Materials>, coordinate: Coordinate) {
+pub fn spawn_jimbo(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    id: ObjectId,
+    coordinate: Coordinate,
+) {
     commands
         .spawn(SpriteBundle {
             material: materials.jimbo_down.clone(),
@@ -277,6 +289,7 @@ pub fn spawn_jimbo(commands: &mut Commands, materials: &Res<Materials>, coordina
         .with(LevelObject)
         .with(Jimbo)
         .with(Opaque)
+        .with(id)
         .with(coordinate)
         .with(crate::Size {
             width: 1.0,
@@ -288,13 +301,11 @@ pub fn spawn_orb(
     commands: &mut Commands,
     materials: &Res<Materials>,
     laser_type: LaserType,
+    state: OrbState,
+    id: ObjectId,
     coordinate: Coordinate,
 ) {
-    let material = match laser_type {
-        LaserType::Red => materials.orb_red_deactivated.clone(),
-        LaserType::Blue => materials.orb_blue_deactivated.clone(),
-        LaserType::Purple => materials.orb_purple_deactivated.clone(),
-    };
+    let material = materials.orb(laser_type, state);
     commands
         .spawn(SpriteBundle {
             material,
@@ -306,11 +317,12 @@ pub fn spawn_orb(
         })
         .with(LevelObject)
         .with(Orb {
-            state: OrbState::Deactivated,
+            state,
             orb_type: laser_type,
         })
         .with(Opaque)
         .with(Movable(false))
+        .with(id)
         .with(coordinate)
         .with(crate::Size {
             width: 0.4,
@@ -318,24 +330,155 @@ pub fn spawn_orb(
         });
 }
 
-pub fn spawn_refactor(
+pub fn spawn_mirror(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    kind: MirrorKind,
+    fixed: bool,
+    id: ObjectId,
+    coordinate: Coordinate,
+) {
+    commands
+        .spawn(SpriteBundle {
+            material: materials.mirror(kind, fixed),
+            sprite: Sprite {
+                size: Default::default(),
+                resize_mode: SpriteResizeMode::Manual,
+            },
+            ..Default::default()
+        })
+        .with(LevelObject)
+        .with(Mirror)
+        .with(Movable(!fixed))
+        .with(id)
+        .with(coordinate)
+        .with(crate::Size {
+            width: 1.0,
+            height: 1.0,
+        });
+}
+
+/// Open gates let beams and Jimbo through, so only closed ones are `Opaque` and `Movable`.
+pub fn spawn_gate(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    open: bool,
+    id: ObjectId,
+    coordinate: Coordinate,
+) {
+    commands
+        .spawn(SpriteBundle {
+            material: materials.gate(open),
+            sprite: Sprite {
+                size: Default::default(),
+                resize_mode: SpriteResizeMode::Manual,
+            },
+            ..Default::default()
+        })
+        .with(LevelObject)
+        .with(Gate { open })
+        .with(id)
+        .with(coordinate)
+        .with(crate::Size {
+            width: 1.0,
+            height: 1.0,
+        });
+    if !open {
+        commands.with(Opaque).with(Movable(false));
+    }
+}
+
+/// Plates never block anything, so they're neither `Opaque` nor `Movable`.
+pub fn spawn_plate(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    pressed: bool,
+    id: ObjectId,
+    coordinate: Coordinate,
+) {
+    commands
+        .spawn(SpriteBundle {
+            material: materials.plate(pressed),
+            sprite: Sprite {
+                size: Default::default(),
+                resize_mode: SpriteResizeMode::Manual,
+            },
+            ..Default::default()
+        })
+        .with(LevelObject)
+        .with(Plate { pressed })
+        .with(id)
+        .with(coordinate)
+        .with(crate::Size {
+            width: 1.0,
+            height: 1.0,
+        });
+}
+
+/// Filters send a beam on in whichever direction it was heading, so they get a laser for each.
+pub fn spawn_filter(
     commands: &mut Commands,
     materials: &Res<Materials>,
     meshes: &mut ResMut<Assets<Mesh>>,
-    directions: Vec<crate::Direction>,
+    material: Handle<ColorMaterial>,
+    fixed: bool,
+    id: ObjectId,
     coordinate: Coordinate,
 ) {
-    let mut material_name =
-        directions
-            .iter()
-            .fold(String::from("refactor"), |mut acc, direction| {
-                acc.push('-');
-                acc.push_str(direction.material_name());
-                acc
-            });
-    material_name.push_str(".png");
+    let filter = commands
+        .spawn(SpriteBundle {
+            material,
+            sprite: Sprite {
+                size: Default::default(),
+                resize_mode: SpriteResizeMode::Manual,
+            },
+            ..Default::default()
+        })
+        .with(LevelObject)
+        .with(Filter)
+        .with(Movable(!fixed))
+        .with(id)
+        .with(coordinate)
+        .with(crate::Size {
+            width: 1.0,
+            height: 1.0,
+        })
+        .current_entity()
+        .unwrap();
+
+    for direction in [
+        crate::Direction::Up,
+        crate::Direction::Right,
+        crate::Direction::Down,
+        crate::Direction::Left,
+    ]
+    .iter()
+    {
+        spawn_laser(
+            commands,
+            materials,
+            meshes,
+            LaserType::RED,
+            coordinate,
+            filter,
+            *direction,
+        );
+    }
+}
 
-    let material = materials.refactors.get(&material_name).unwrap();
+pub fn spawn_refactor(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    kind: RefactorKind,
+    id: ObjectId,
+    coordinate: Coordinate,
+) {
+    let material_name = kind.material_name();
+    let material = materials
+        .refactors
+        .get(&material_name)
+        .unwrap_or_else(|| panic!("Missing refactor texture: assets/{}", material_name));
 
     let source = commands
         .spawn(SpriteBundle {
@@ -348,6 +491,7 @@ pub fn spawn_refactor(
         })
         .with(LevelObject)
         .with(Movable(true))
+        .with(id)
         .with(coordinate)
         .with(crate::Size {
             width: 0.9,
@@ -356,20 +500,21 @@ pub fn spawn_refactor(
         .current_entity()
         .unwrap();
 
-    let refactor_directions = directions
+    let refactor_directions = kind
+        .directions()
         .into_iter()
         .map(|direction| {
             let outbound_laser = spawn_laser(
                 commands,
                 materials,
                 meshes,
-                LaserType::Red,
+                LaserType::RED,
                 coordinate,
                 source,
+                direction,
             );
             RefactorDirection {
                 direction,
-                inbound_lasers: HashSet::new(),
                 outbound_laser,
             }
         })
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever a change to the save file can't be covered by `#[serde(default)]` alone.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for ProgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+impl std::error::Error for ProgressError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelProgress {
+    #[serde(default)]
+    pub completed: bool,
+    /// The fewest moves the level has been completed in.
+    #[serde(default)]
+    pub best_moves: Option<usize>,
+}
+
+/// The player's progress, saved as RON so it carries over between launches. Levels are keyed by
+/// their file so it holds up if packs are reordered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    pub version: u32,
+    #[serde(default)]
+    pub levels: BTreeMap<PathBuf, LevelProgress>,
+    #[serde(default)]
+    pub last_level: Option<PathBuf>,
+    /// Where the progress is saved to, if anywhere.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress {
+            version: SAVE_VERSION,
+            levels: BTreeMap::new(),
+            last_level: None,
+            path: None,
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME/one-laser`, falling back to `~/.local/share` when it isn't set.
+pub fn data_dir() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_dir.join("one-laser"))
+}
+
+impl Progress {
+    pub fn default_path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("save.ron"))
+    }
+
+    /// Loads the save file at the default path. A missing file is a fresh start, while a broken one
+    /// is reported and left alone so saving doesn't clobber it.
+    pub fn load_or_default() -> Progress {
+        let path = match Progress::default_path() {
+            Some(path) => path,
+            None => {
+                eprintln!("Couldn't find a data directory, progress won't be saved");
+                return Progress::default();
+            }
+        };
+
+        if !path.exists() {
+            return Progress {
+                path: Some(path),
+                ..Default::default()
+            };
+        }
+
+        match Progress::load(&path) {
+            Ok(progress) => progress,
+            Err(err) => {
+                eprintln!("{}, progress won't be saved", err);
+                Progress::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Progress, ProgressError> {
+        let error = |reason: String| ProgressError {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let contents = fs::read_to_string(path)
+            .map_err(|err| error(format!("failed to read save file: {}", err)))?;
+        let mut progress: Progress = ron::de::from_str(&contents)
+            .map_err(|err| error(format!("failed to parse save file: {}", err)))?;
+
+        if progress.version > SAVE_VERSION {
+            return Err(error(format!(
+                "save file version {} is newer than this game supports ({})",
+                progress.version, SAVE_VERSION
+            )));
+        }
+
+        progress.version = SAVE_VERSION;
+        progress.path = Some(path.to_path_buf());
+        Ok(progress)
+    }
+
+    /// Writes the save file, if there is one. The old file is only replaced once the new one is
+    /// fully written.
+    pub fn save(&self) -> Result<(), ProgressError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let error = |reason: String| ProgressError {
+            path: path.clone(),
+            reason,
+        };
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| error(format!("failed to serialize save file: {}", err)))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| error(format!("failed to create save directory: {}", err)))?;
+        }
+
+        let temp_path = path.with_extension("ron.tmp");
+        fs::write(&temp_path, contents)
+            .and_then(|_| fs::rename(&temp_path, path))
+            .map_err(|err| error(format!("failed to write save file: {}", err)))
+    }
+
+    pub fn is_completed(&self, level: &Path) -> bool {
+        matches!(self.levels.get(level), Some(progress) if progress.completed)
+    }
+
+    pub fn best_moves(&self, level: &Path) -> Option<usize> {
+        self.levels
+            .get(level)
+            .and_then(|progress| progress.best_moves)
+    }
+
+    pub fn record_completion(&mut self, level: &Path, moves: usize) {
+        let progress = self.levels.entry(level.to_path_buf()).or_default();
+        progress.completed = true;
+        progress.best_moves = Some(progress.best_moves.map_or(moves, |best| best.min(moves)));
+    }
+}
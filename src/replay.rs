@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::progress;
+use crate::LevelCommand;
+
+pub const DEFAULT_MOVES_PER_SECOND: f32 = 4.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Every command given during an attempt at a level, from the moment it was loaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub level: PathBuf,
+    /// Hash of the level file, so a replay isn't played back against a level that has changed.
+    pub level_hash: u64,
+    pub commands: Vec<LevelCommand>,
+}
+
+impl Replay {
+    pub fn new(level: &Path, contents: &str) -> Self {
+        Replay {
+            level: level.to_path_buf(),
+            level_hash: level_hash(contents),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Where the replay for a level is saved: `replays/<level name>-<level hash>.ron` in the data
+    /// directory.
+    pub fn default_path(level: &Path, level_hash: u64) -> Option<PathBuf> {
+        let name = saved_file_stem(level, level_hash)?;
+        progress::data_dir().map(|dir| dir.join("replays").join(name).with_extension("ron"))
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+        let error = |reason: String| ReplayError {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let contents = fs::read_to_string(path)
+            .map_err(|err| error(format!("failed to read replay: {}", err)))?;
+        ron::de::from_str(&contents)
+            .map_err(|err| error(format!("failed to parse replay: {}", err)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        let error = |reason: String| ReplayError {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let contents = ron::ser::to_string(self)
+            .map_err(|err| error(format!("failed to serialize replay: {}", err)))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| error(format!("failed to create replay directory: {}", err)))?;
+        }
+        fs::write(path, contents).map_err(|err| error(format!("failed to write replay: {}", err)))
+    }
+}
+
+/// 64-bit FNV-1a, which is stable across platforms and releases unlike `DefaultHasher`.
+pub fn level_hash(contents: &str) -> u64 {
+    contents.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The name files saved for a level go under. The hash keeps levels that share a file name, from
+/// different packs or opened straight from the command line, from overwriting each other's files.
+pub fn saved_file_stem(level: &Path, level_hash: u64) -> Option<String> {
+    let name = level.file_stem()?.to_str()?;
+    Some(format!("{}-{:016x}", name, level_hash))
+}
+
+/// The attempt currently being played.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayRecorder(pub Replay);
+
+/// Commands still to be played back, which take the place of the player's input until they run out.
+#[derive(Debug, Clone)]
+pub struct Playback {
+    pub commands: VecDeque<LevelCommand>,
+    pub moves_per_second: f32,
+    /// Seconds until the next command is played.
+    pub countdown: f32,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            commands: VecDeque::new(),
+            moves_per_second: DEFAULT_MOVES_PER_SECOND,
+            countdown: 0.0,
+        }
+    }
+}
+
+impl Playback {
+    pub fn is_playing(&self) -> bool {
+        !self.commands.is_empty()
+    }
+
+    pub fn play(&mut self, commands: impl IntoIterator<Item = LevelCommand>) {
+        self.commands = commands.into_iter().collect();
+        self.countdown = 1.0 / self.moves_per_second;
+    }
+
+    pub fn stop(&mut self) {
+        self.commands.clear();
+    }
+
+    /// Advances playback by `seconds`, returning the command due to be played, if any.
+    pub fn tick(&mut self, seconds: f32) -> Option<LevelCommand> {
+        if !self.is_playing() {
+            return None;
+        }
+
+        self.countdown -= seconds;
+        if self.countdown > 0.0 {
+            return None;
+        }
+
+        self.countdown = self.countdown.max(0.0) + 1.0 / self.moves_per_second;
+        self.commands.pop_front()
+    }
+}
@@ -1,6 +1,18 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
 
+use crate::game_state::Snapshot;
+use crate::gamepad::GamepadInput;
+use crate::level::{FilterMode, LevelDescription, LevelInfo, MirrorKind};
+use crate::replay::{Playback, ReplayRecorder};
+use crate::system_stages::controls::Rebinding;
+use crate::system_stages::editor::Editor;
+use crate::system_stages::hot_reload::LevelWatcher;
+use crate::system_stages::input::GamepadFocus;
+use crate::system_stages::walk::Walk;
 use crate::*;
 
 pub struct ResourcesPlugin;
@@ -8,45 +20,57 @@ pub struct ResourcesPlugin;
 impl Plugin for ResourcesPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_resource(AppState::LevelSelect);
+        app.add_resource(Editor::default());
         app.add_resource(EntityTracker::default());
+        app.add_resource(GameState::default());
+        app.add_resource(GamepadFocus::default());
+        app.add_resource(GamepadInput::default());
+        // `main` adds its own from the command line.
+        if !app.resources().contains::<LevelPacks>() {
+            app.add_resource(LevelPacks::load_dir(Path::new(level_pack::PACKS_DIR)));
+        }
+        if !app.resources().contains::<StartLevel>() {
+            app.add_resource(StartLevel::default());
+        }
+        app.add_resource(KeyBindings::load_or_default());
+        app.add_resource(LevelDescription::default());
+        app.add_resource(LevelWatcher::default());
+        app.add_resource(LevelInfo::default());
         app.add_resource(LevelSize::default());
+        app.add_resource(MoveLog::default());
+        app.add_resource(Playback::default());
+        app.add_resource(Progress::load_or_default());
+        app.add_resource(Rebinding::default());
+        app.add_resource(RedoBuffer::default());
+        app.add_resource(ReplayRecorder::default());
         app.add_resource(TurnCounter::default());
         app.add_resource(UndoBuffer::default());
+        app.add_resource(Walk::default());
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Materials {
+    pub button_focused: Handle<ColorMaterial>,
     pub crate_material: Handle<ColorMaterial>,
+    pub filters: HashMap<(LaserType, FilterMode, bool), Handle<ColorMaterial>>,
+    pub gate_closed: Handle<ColorMaterial>,
+    pub gate_open: Handle<ColorMaterial>,
     pub jimbo_down: Handle<ColorMaterial>,
     pub jimbo_right: Handle<ColorMaterial>,
     pub jimbo_left: Handle<ColorMaterial>,
     pub jimbo_up: Handle<ColorMaterial>,
     pub tile: Handle<ColorMaterial>,
-    pub laser_blue: Handle<ColorMaterial>,
-    pub laser_purple: Handle<ColorMaterial>,
-    pub laser_red: Handle<ColorMaterial>,
-    pub laser_source_blue_right: Handle<ColorMaterial>,
-    pub laser_source_blue_down: Handle<ColorMaterial>,
-    pub laser_source_blue_left: Handle<ColorMaterial>,
-    pub laser_source_blue_up: Handle<ColorMaterial>,
-    pub laser_source_red_right: Handle<ColorMaterial>,
-    pub laser_source_red_down: Handle<ColorMaterial>,
-    pub laser_source_red_left: Handle<ColorMaterial>,
-    pub laser_source_red_up: Handle<ColorMaterial>,
-    pub laser_source_purple_right: Handle<ColorMaterial>,
-    pub laser_source_purple_down: Handle<ColorMaterial>,
-    pub laser_source_purple_left: Handle<ColorMaterial>,
-    pub laser_source_purple_up: Handle<ColorMaterial>,
-    pub orb_blue_activated: Handle<ColorMaterial>,
-    pub orb_blue_deactivated: Handle<ColorMaterial>,
-    pub orb_blue_destroyed: Handle<ColorMaterial>,
-    pub orb_red_activated: Handle<ColorMaterial>,
-    pub orb_red_deactivated: Handle<ColorMaterial>,
-    pub orb_red_destroyed: Handle<ColorMaterial>,
-    pub orb_purple_activated: Handle<ColorMaterial>,
-    pub orb_purple_deactivated: Handle<ColorMaterial>,
-    pub orb_purple_destroyed: Handle<ColorMaterial>,
+    /// Generated for every color, since levels can mix any of them.
+    pub lasers: HashMap<LaserType, Handle<ColorMaterial>>,
+    pub laser_sources: HashMap<(LaserType, crate::Direction, bool), Handle<ColorMaterial>>,
+    pub mirror_backslash: Handle<ColorMaterial>,
+    pub mirror_fixed_backslash: Handle<ColorMaterial>,
+    pub mirror_fixed_slash: Handle<ColorMaterial>,
+    pub mirror_slash: Handle<ColorMaterial>,
+    pub orbs: HashMap<(LaserType, OrbState), Handle<ColorMaterial>>,
+    pub plate_pressed: Handle<ColorMaterial>,
+    pub plate_raised: Handle<ColorMaterial>,
     pub refactor_right: Handle<ColorMaterial>,
     pub refactor_down: Handle<ColorMaterial>,
     pub refactor_left: Handle<ColorMaterial>,
@@ -55,12 +79,85 @@ pub struct Materials {
     pub wall: Handle<ColorMaterial>,
 }
 
+impl Materials {
+    pub fn gate(&self, open: bool) -> Handle<ColorMaterial> {
+        if open {
+            self.gate_open.clone()
+        } else {
+            self.gate_closed.clone()
+        }
+    }
+
+    pub fn jimbo(&self, facing: crate::Direction) -> Handle<ColorMaterial> {
+        match facing {
+            crate::Direction::Up => self.jimbo_up.clone(),
+            crate::Direction::Right => self.jimbo_right.clone(),
+            crate::Direction::Down => self.jimbo_down.clone(),
+            crate::Direction::Left => self.jimbo_left.clone(),
+        }
+    }
+
+    pub fn filter(
+        &self,
+        laser_type: LaserType,
+        mode: FilterMode,
+        fixed: bool,
+    ) -> Handle<ColorMaterial> {
+        self.filters[&(laser_type, mode, fixed)].clone()
+    }
+
+    pub fn laser(&self, laser_type: LaserType) -> Handle<ColorMaterial> {
+        self.lasers[&laser_type].clone()
+    }
+
+    pub fn laser_source(
+        &self,
+        laser_type: LaserType,
+        direction: crate::Direction,
+        on: bool,
+    ) -> Handle<ColorMaterial> {
+        self.laser_sources[&(laser_type, direction, on)].clone()
+    }
+
+    pub fn mirror(&self, kind: MirrorKind, fixed: bool) -> Handle<ColorMaterial> {
+        match (kind, fixed) {
+            (MirrorKind::Slash, false) => self.mirror_slash.clone(),
+            (MirrorKind::Slash, true) => self.mirror_fixed_slash.clone(),
+            (MirrorKind::Backslash, false) => self.mirror_backslash.clone(),
+            (MirrorKind::Backslash, true) => self.mirror_fixed_backslash.clone(),
+        }
+    }
+
+    pub fn orb(&self, orb_type: LaserType, state: OrbState) -> Handle<ColorMaterial> {
+        self.orbs[&(orb_type, state)].clone()
+    }
+
+    pub fn plate(&self, pressed: bool) -> Handle<ColorMaterial> {
+        if pressed {
+            self.plate_pressed.clone()
+        } else {
+            self.plate_raised.clone()
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AppState {
     LevelSelect,
     Level(usize),
+    /// The level stays on screen under the completion overlay until the player picks what's next.
+    LevelCompleted(usize),
+    Editor,
+    /// The controls screen, where keys are rebound.
+    Controls,
 }
 
+/// The level to open instead of the level select at startup.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct StartLevel(pub Option<usize>);
+
+/// Every entity in each cell, which can be several at once, such as the tile, a plate and the crate
+/// on it.
 #[derive(Debug, Clone, Default)]
 pub struct EntityTracker(pub HashMap<Coordinate, Vec<Entity>>);
 
@@ -70,10 +167,72 @@ pub struct LevelSize {
     pub height: u32,
 }
 
+/// The moves that led to the current state in LURD notation, see `lurd`. Moves that were undone
+/// or restarted over stay in the log until a new move replaces them, so a snapshot only has to
+/// remember which part of it was current.
+#[derive(Debug, Clone, Default)]
+pub struct MoveLog {
+    log: String,
+    current: Range<usize>,
+}
+
+impl MoveLog {
+    pub fn moves(&self) -> &str {
+        &self.log[self.current.clone()]
+    }
+
+    /// Adds a move, dropping any undone ones it replaces.
+    pub fn push(&mut self, letter: char) {
+        self.log.truncate(self.current.end);
+        self.log.push(letter);
+        self.current.end = self.log.len();
+    }
+
+    /// Starts an empty run of moves, keeping the ones before it for undo.
+    pub fn restart(&mut self) {
+        self.current.start = self.current.end;
+    }
+
+    pub fn clear(&mut self) {
+        *self = MoveLog::default();
+    }
+
+    pub fn current(&self) -> Range<usize> {
+        self.current.clone()
+    }
+
+    /// Goes back to a part of the log taken from `current` since the last `clear`.
+    pub fn restore(&mut self, current: Range<usize>) {
+        self.current = current;
+    }
+}
+
 #[derive(Debug, Copy, Clone, Default)]
 pub struct TurnCounter(pub usize);
 
-pub type UndoFn = Box<dyn FnOnce(&mut World) + Send + Sync + 'static>;
+/// The level as it was at some turn, so it can be returned to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnSnapshot {
+    pub turn: usize,
+    /// The part of the `MoveLog` that was current.
+    pub moves: Range<usize>,
+    pub state: Snapshot,
+}
+
+impl TurnSnapshot {
+    pub fn new(turn_counter: &TurnCounter, move_log: &MoveLog, game_state: &GameState) -> Self {
+        TurnSnapshot {
+            turn: turn_counter.0,
+            moves: move_log.current(),
+            state: game_state.snapshot(),
+        }
+    }
+}
 
-#[derive(Default)]
-pub struct UndoBuffer(pub Vec<(usize, UndoFn)>);
+/// The level before each turn, most recent last.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UndoBuffer(pub Vec<TurnSnapshot>);
+
+/// The level before each undo, most recently undone last.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RedoBuffer(pub Vec<TurnSnapshot>);
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use std::collections::HashMap;
 
+use crate::level::FilterMode;
 use crate::*;
 
 pub struct StartupSystemPlugin;
@@ -13,7 +14,23 @@ impl Plugin for StartupSystemPlugin {
         app.add_startup_system_to_stage("materials", create_materials.system());
 
         app.add_startup_stage("initial_load", SystemStage::serial());
-        app.add_startup_system_to_stage("initial_load", load_level_selector.system());
+        app.add_startup_system_to_stage("initial_load", initial_level_selector.system());
+    }
+}
+
+fn initial_level_selector(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    materials: ResMut<Assets<ColorMaterial>>,
+    packs: Res<LevelPacks>,
+    progress: Res<Progress>,
+    start_level: Res<StartLevel>,
+    mut state_change_events: ResMut<Events<AppStateChangeEvent>>,
+) {
+    load_level_selector(commands, asset_server, materials, &packs, &progress, None);
+
+    if let Some(level) = start_level.0 {
+        state_change_events.send(AppStateChangeEvent(AppState::Level(level)));
     }
 }
 
@@ -21,6 +38,9 @@ pub fn load_level_selector(
     commands: &mut Commands,
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    packs: &LevelPacks,
+    progress: &Progress,
+    error: Option<&map::LevelParseError>,
 ) {
     commands
         .spawn(CameraUiBundle::default())
@@ -56,40 +76,148 @@ pub fn load_level_selector(
                 },
                 ..Default::default()
             });
+
+            if let Some(error) = error {
+                parent.spawn(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    text: Text {
+                        value: error.to_string(),
+                        font: asset_server.load("fonts/Helvetica.ttf"),
+                        style: TextStyle {
+                            font_size: 20.0,
+                            color: Color::rgb(0.9, 0.3, 0.3),
+                            alignment: TextAlignment {
+                                horizontal: HorizontalAlign::Center,
+                                vertical: VerticalAlign::Center,
+                            },
+                        },
+                    },
+                    ..Default::default()
+                });
+            }
         })
         .with_children(|parent| {
+            let mut level_index = 0;
+            for pack in packs.packs.iter() {
+                parent.spawn(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    text: Text {
+                        value: pack.name.clone(),
+                        font: asset_server.load("fonts/Helvetica.ttf"),
+                        style: TextStyle {
+                            font_size: 25.0,
+                            color: Color::WHITE,
+                            alignment: TextAlignment {
+                                horizontal: HorizontalAlign::Center,
+                                vertical: VerticalAlign::Center,
+                            },
+                        },
+                    },
+                    ..Default::default()
+                });
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            size: bevy::prelude::Size::new(Val::Percent(100.0), Val::Auto),
+                            flex_direction: FlexDirection::Row,
+                            justify_content: JustifyContent::FlexStart,
+                            align_items: AlignItems::FlexEnd,
+                            flex_wrap: FlexWrap::WrapReverse,
+                            ..Default::default()
+                        },
+                        material: materials.add(Color::NONE.into()),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        for (index_in_pack, level) in pack.levels.iter().enumerate() {
+                            let title = level
+                                .title
+                                .clone()
+                                .unwrap_or_else(|| (index_in_pack + 1).to_string());
+                            let color = if progress.is_completed(&level.file) {
+                                Color::rgb(0.2, 0.5, 0.2)
+                            } else {
+                                Color::rgb(0.6, 0.2, 0.2)
+                            };
+                            parent
+                                .spawn(ButtonBundle {
+                                    style: Style {
+                                        size: bevy::prelude::Size::new(
+                                            Val::Px(150.0),
+                                            Val::Px(150.0),
+                                        ),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        margin: Rect::all(Val::Px(10.0)),
+                                        ..Default::default()
+                                    },
+                                    material: materials.add(color.into()),
+                                    ..Default::default()
+                                })
+                                .with(AppStateChangeEvent(AppState::Level(level_index)))
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle {
+                                        text: Text {
+                                            value: title,
+                                            font: asset_server.load("fonts/Helvetica.ttf"),
+                                            style: TextStyle {
+                                                font_size: 20.0,
+                                                color: Color::WHITE,
+                                                alignment: TextAlignment {
+                                                    horizontal: HorizontalAlign::Center,
+                                                    vertical: VerticalAlign::Center,
+                                                },
+                                            },
+                                        },
+                                        ..Default::default()
+                                    });
+                                });
+                            level_index += 1;
+                        }
+                    });
+            }
+
             parent
                 .spawn(NodeBundle {
                     style: Style {
-                        size: bevy::prelude::Size::new(Val::Percent(100.0), Val::Percent(100.0)),
                         flex_direction: FlexDirection::Row,
-                        justify_content: JustifyContent::FlexStart,
-                        align_items: AlignItems::FlexEnd,
-                        flex_wrap: FlexWrap::WrapReverse,
                         ..Default::default()
                     },
                     material: materials.add(Color::NONE.into()),
                     ..Default::default()
                 })
                 .with_children(|parent| {
-                    for (level_index, _) in LEVELS.iter().enumerate() {
+                    let material = materials.add(Color::rgb(0.2, 0.2, 0.6).into());
+                    for (label, next_state) in [
+                        ("New Level", AppState::Editor),
+                        ("Controls", AppState::Controls),
+                    ]
+                    .iter()
+                    {
                         parent
                             .spawn(ButtonBundle {
                                 style: Style {
-                                    size: bevy::prelude::Size::new(Val::Px(200.0), Val::Px(200.0)),
+                                    size: bevy::prelude::Size::new(Val::Px(150.0), Val::Px(50.0)),
                                     justify_content: JustifyContent::Center,
                                     align_items: AlignItems::Center,
                                     margin: Rect::all(Val::Px(10.0)),
                                     ..Default::default()
                                 },
-                                material: materials.add(Color::rgb(0.6, 0.2, 0.2).into()),
+                                material: material.clone(),
                                 ..Default::default()
                             })
-                            .with(AppStateChangeEvent(AppState::Level(level_index)))
+                            .with(AppStateChangeEvent(*next_state))
                             .with_children(|parent| {
                                 parent.spawn(TextBundle {
                                     text: Text {
-                                        value: (level_index + 1).to_string(),
+                                        value: label.to_string(),
                                         font: asset_server.load("fonts/Helvetica.ttf"),
                                         style: TextStyle {
                                             font_size: 20.0,
@@ -112,51 +240,48 @@ fn initial_setup(commands: &mut Commands) {
     commands.spawn(Camera2dBundle::default());
 }
 
+/// How much of its color an orb shows until it's lit, and a laser source while it's off.
+const UNLIT_BRIGHTNESS: f32 = 0.3;
+
+fn tint([r, g, b]: [u8; 3], brightness: f32) -> Color {
+    let scale = |channel: u8| (channel as f32 * brightness) as u8;
+    Color::rgb_u8(scale(r), scale(g), scale(b))
+}
+
 fn create_materials(
     commands: &mut Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
 ) {
+    // A ring around the edge of the tile, so whatever is on a plate stays visible.
+    let plate_texture: Handle<Texture> = asset_server.load("plate.png");
     let mut mats = Materials {
+        button_focused: materials.add(Color::rgb(0.8, 0.6, 0.2).into()),
         crate_material: materials.add(asset_server.load("crate.png").into()),
+        filters: HashMap::new(),
+        gate_closed: materials.add(asset_server.load("gate-closed.png").into()),
+        gate_open: materials.add(asset_server.load("gate-open.png").into()),
         jimbo_down: materials.add(asset_server.load("character-down.png").into()),
         jimbo_left: materials.add(asset_server.load("character-left.png").into()),
         jimbo_up: materials.add(asset_server.load("character-up.png").into()),
         jimbo_right: materials.add(asset_server.load("character-right.png").into()),
         tile: materials.add(Color::rgb_u8(2, 95, 19).into()),
-        laser_blue: materials.add(Color::rgb_u8(24, 107, 255).into()),
-        laser_purple: materials.add(Color::rgb_u8(112, 8, 192).into()),
-        laser_red: materials.add(Color::rgb_u8(232, 39, 39).into()),
-        laser_source_blue_right: materials
-            .add(asset_server.load("laser-source-blue-right.png").into()),
-        laser_source_blue_down: materials
-            .add(asset_server.load("laser-source-blue-down.png").into()),
-        laser_source_blue_left: materials
-            .add(asset_server.load("laser-source-blue-left.png").into()),
-        laser_source_blue_up: materials.add(asset_server.load("laser-source-blue-up.png").into()),
-        laser_source_red_right: materials
-            .add(asset_server.load("laser-source-red-right.png").into()),
-        laser_source_red_down: materials.add(asset_server.load("laser-source-red-down.png").into()),
-        laser_source_red_left: materials.add(asset_server.load("laser-source-red-left.png").into()),
-        laser_source_red_up: materials.add(asset_server.load("laser-source-red-up.png").into()),
-        laser_source_purple_right: materials
-            .add(asset_server.load("laser-source-purple-right.png").into()),
-        laser_source_purple_down: materials
-            .add(asset_server.load("laser-source-purple-down.png").into()),
-        laser_source_purple_left: materials
-            .add(asset_server.load("laser-source-purple-left.png").into()),
-        laser_source_purple_up: materials
-            .add(asset_server.load("laser-source-purple-up.png").into()),
-        orb_blue_activated: materials.add(asset_server.load("orb-blue-activated.png").into()),
-        orb_blue_deactivated: materials.add(asset_server.load("orb-blue-deactivated.png").into()),
-        orb_blue_destroyed: materials.add(asset_server.load("orb-blue-destroyed.png").into()),
-        orb_red_activated: materials.add(asset_server.load("orb-red-activated.png").into()),
-        orb_red_deactivated: materials.add(asset_server.load("orb-red-deactivated.png").into()),
-        orb_red_destroyed: materials.add(asset_server.load("orb-red-destroyed.png").into()),
-        orb_purple_activated: materials.add(asset_server.load("orb-purple-activated.png").into()),
-        orb_purple_deactivated: materials
-            .add(asset_server.load("orb-purple-deactivated.png").into()),
-        orb_purple_destroyed: materials.add(asset_server.load("orb-purple-destroyed.png").into()),
+        lasers: HashMap::new(),
+        laser_sources: HashMap::new(),
+        mirror_backslash: materials.add(asset_server.load("mirror-backslash.png").into()),
+        mirror_fixed_backslash: materials
+            .add(asset_server.load("mirror-fixed-backslash.png").into()),
+        mirror_fixed_slash: materials.add(asset_server.load("mirror-fixed-slash.png").into()),
+        mirror_slash: materials.add(asset_server.load("mirror-slash.png").into()),
+        orbs: HashMap::new(),
+        plate_pressed: materials.add(ColorMaterial::modulated_texture(
+            plate_texture.clone(),
+            Color::rgb_u8(220, 200, 90),
+        )),
+        plate_raised: materials.add(ColorMaterial::modulated_texture(
+            plate_texture,
+            Color::rgb_u8(110, 110, 120),
+        )),
         refactor_right: materials.add(asset_server.load("refactor-right.png").into()),
         refactor_down: materials.add(asset_server.load("refactor-down.png").into()),
         refactor_left: materials.add(asset_server.load("refactor-left.png").into()),
@@ -165,6 +290,77 @@ fn create_materials(
         wall: materials.add(asset_server.load("wall.png").into()),
     };
 
+    // White masks tinted with each color, so the same art serves every color a level can use.
+    let laser_source_textures = [
+        (
+            crate::Direction::Up,
+            asset_server.load("laser-source-up.png"),
+        ),
+        (
+            crate::Direction::Right,
+            asset_server.load("laser-source-right.png"),
+        ),
+        (
+            crate::Direction::Down,
+            asset_server.load("laser-source-down.png"),
+        ),
+        (
+            crate::Direction::Left,
+            asset_server.load("laser-source-left.png"),
+        ),
+    ];
+    let orb_texture: Handle<Texture> = asset_server.load("orb.png");
+    let destroyed_orb_texture: Handle<Texture> = asset_server.load("orb-destroyed.png");
+    let filter_textures = [
+        (FilterMode::Pass, false, asset_server.load("filter.png")),
+        (
+            FilterMode::Convert,
+            false,
+            asset_server.load("filter-converting.png"),
+        ),
+        (
+            FilterMode::Pass,
+            true,
+            asset_server.load("filter-fixed.png"),
+        ),
+        (
+            FilterMode::Convert,
+            true,
+            asset_server.load("filter-fixed-converting.png"),
+        ),
+    ];
+
+    for laser_type in LaserType::ALL.iter().copied() {
+        let lit = tint(laser_type.rgb(), 1.0);
+        let unlit = tint(laser_type.rgb(), UNLIT_BRIGHTNESS);
+        mats.lasers.insert(laser_type, materials.add(lit.into()));
+
+        for (direction, texture) in laser_source_textures.iter() {
+            for (on, color) in [(false, unlit), (true, lit)].iter() {
+                let source = ColorMaterial::modulated_texture(texture.clone(), *color);
+                mats.laser_sources
+                    .insert((laser_type, *direction, *on), materials.add(source));
+            }
+        }
+
+        for (state, texture, color) in [
+            (OrbState::Deactivated, &orb_texture, unlit),
+            (OrbState::Activated, &orb_texture, lit),
+            (OrbState::Destroyed, &destroyed_orb_texture, unlit),
+        ]
+        .iter()
+        {
+            let orb = ColorMaterial::modulated_texture((*texture).clone(), *color);
+            mats.orbs.insert((laser_type, *state), materials.add(orb));
+        }
+
+        for (mode, fixed, texture) in filter_textures.iter() {
+            let filter = ColorMaterial::modulated_texture(texture.clone(), lit);
+            mats.filters
+                .insert((laser_type, *mode, *fixed), materials.add(filter));
+        }
+    }
+
     for entry in std::fs::read_dir("assets").unwrap() {
         let entry = entry.unwrap();
         let asset = entry.file_name();
@@ -0,0 +1,303 @@
+use bevy::ecs::Component;
+use bevy::prelude::*;
+
+use crate::key_bindings::{self, Action, Binding};
+use crate::*;
+
+pub const NAME: &str = "controls";
+
+pub fn stage() -> SystemStage {
+    let mut stage = SystemStage::parallel();
+    stage.add_system(detect_controls_command.system());
+    stage.add_system(capture_binding.system());
+    stage.add_system(update_binding_texts.system());
+    stage
+}
+
+/// The action waiting for a key to be bound to it on the controls screen.
+#[derive(Debug, Clone, Default)]
+pub struct Rebinding {
+    pub waiting: Option<Action>,
+}
+
+/// What a button on the controls screen does.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ControlsCommand {
+    /// Binds the next key pressed to the action.
+    Add(Action),
+    Clear(Action),
+    ResetAll,
+}
+
+/// Marks the text listing the keys bound to an action.
+#[derive(Debug, Copy, Clone)]
+pub struct BindingText(pub Action);
+
+pub fn spawn_controls(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    bindings: &KeyBindings,
+) {
+    let font = asset_server.load("fonts/Helvetica.ttf");
+    let text = |value: String, font_size: f32| Text {
+        value,
+        font: font.clone(),
+        style: TextStyle {
+            font_size,
+            color: Color::WHITE,
+            alignment: TextAlignment {
+                horizontal: HorizontalAlign::Left,
+                vertical: VerticalAlign::Center,
+            },
+        },
+    };
+    let button_material = materials.add(Color::rgb(0.6, 0.2, 0.2).into());
+    let none = materials.add(Color::NONE.into());
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: bevy::prelude::Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::FlexStart,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: none.clone(),
+            ..Default::default()
+        })
+        .with(UiObject)
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(20.0)),
+                    ..Default::default()
+                },
+                text: text("Controls".to_string(), 30.0),
+                ..Default::default()
+            });
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: bevy::prelude::Size::new(Val::Percent(100.0), Val::Auto),
+                        flex_direction: FlexDirection::Row,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::FlexEnd,
+                        flex_wrap: FlexWrap::WrapReverse,
+                        ..Default::default()
+                    },
+                    material: none.clone(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for action in Action::all() {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    size: bevy::prelude::Size::new(Val::Px(620.0), Val::Px(30.0)),
+                                    flex_direction: FlexDirection::Row,
+                                    align_items: AlignItems::Center,
+                                    margin: Rect::all(Val::Px(4.0)),
+                                    ..Default::default()
+                                },
+                                material: none.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle {
+                                    style: Style {
+                                        size: bevy::prelude::Size::new(Val::Px(160.0), Val::Auto),
+                                        ..Default::default()
+                                    },
+                                    text: text(action.to_string(), 18.0),
+                                    ..Default::default()
+                                });
+                                parent
+                                    .spawn(TextBundle {
+                                        style: Style {
+                                            size: bevy::prelude::Size::new(
+                                                Val::Px(300.0),
+                                                Val::Auto,
+                                            ),
+                                            ..Default::default()
+                                        },
+                                        text: text(binding_text(bindings.get(action), false), 18.0),
+                                        ..Default::default()
+                                    })
+                                    .with(BindingText(action));
+                                spawn_controls_button(
+                                    parent,
+                                    button_material.clone(),
+                                    text("Add".to_string(), 16.0),
+                                    ControlsCommand::Add(action),
+                                );
+                                spawn_controls_button(
+                                    parent,
+                                    button_material.clone(),
+                                    text("Clear".to_string(), 16.0),
+                                    ControlsCommand::Clear(action),
+                                );
+                            });
+                    }
+                });
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    material: none.clone(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    spawn_controls_button(
+                        parent,
+                        button_material.clone(),
+                        text("Reset All".to_string(), 16.0),
+                        ControlsCommand::ResetAll,
+                    );
+                    spawn_controls_button(
+                        parent,
+                        button_material.clone(),
+                        text("Back".to_string(), 16.0),
+                        AppStateChangeEvent(AppState::LevelSelect),
+                    );
+                });
+        });
+}
+
+fn spawn_controls_button(
+    parent: &mut ChildBuilder,
+    material: Handle<ColorMaterial>,
+    label: Text,
+    action: impl Component,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                size: bevy::prelude::Size::new(Val::Px(80.0), Val::Px(26.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                margin: Rect::all(Val::Px(4.0)),
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        })
+        .with(action)
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: label,
+                ..Default::default()
+            });
+        });
+}
+
+fn detect_controls_command(
+    state: Res<AppState>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+    interaction_q: Query<(&Interaction, &ControlsCommand), Changed<Interaction>>,
+) {
+    if *state != AppState::Controls {
+        rebinding.waiting = None;
+        return;
+    }
+
+    for (interaction, command) in interaction_q.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match *command {
+            ControlsCommand::Add(action) => {
+                rebinding.waiting = Some(action);
+                continue;
+            }
+            ControlsCommand::Clear(action) => bindings.clear(action),
+            ControlsCommand::ResetAll => {
+                *bindings = KeyBindings {
+                    path: bindings.path.clone(),
+                    ..Default::default()
+                }
+            }
+        }
+
+        rebinding.waiting = None;
+        if let Err(err) = bindings.save() {
+            eprintln!("{}", err);
+        }
+    }
+}
+
+/// Binds the next key pressed to the action waiting for one. Shift on its own can't be bound, it
+/// only modifies the key pressed with it, and Escape cancels.
+fn capture_binding(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let action = match rebinding.waiting {
+        Some(action) => action,
+        None => return,
+    };
+
+    let key = match keyboard_input
+        .get_just_pressed()
+        .find(|key| !matches!(key, KeyCode::LShift | KeyCode::RShift))
+    {
+        Some(key) => *key,
+        None => return,
+    };
+
+    rebinding.waiting = None;
+    if key == KeyCode::Escape {
+        return;
+    }
+
+    let binding = if key_bindings::shift_pressed(&keyboard_input) {
+        Binding::Shift(key)
+    } else {
+        Binding::Key(key)
+    };
+    bindings.add(action, binding);
+    if let Err(err) = bindings.save() {
+        eprintln!("{}", err);
+    }
+}
+
+fn update_binding_texts(
+    state: Res<AppState>,
+    bindings: Res<KeyBindings>,
+    rebinding: Res<Rebinding>,
+    mut q: Query<(&BindingText, &mut Text)>,
+) {
+    if *state != AppState::Controls {
+        return;
+    }
+
+    for (BindingText(action), mut text) in q.iter_mut() {
+        let value = binding_text(bindings.get(*action), rebinding.waiting == Some(*action));
+        if text.value != value {
+            text.value = value;
+        }
+    }
+}
+
+fn binding_text(bindings: &[Binding], waiting: bool) -> String {
+    if waiting {
+        return "Press a key, Esc cancels".to_string();
+    }
+    if bindings.is_empty() {
+        return "Unbound".to_string();
+    }
+    bindings
+        .iter()
+        .map(|binding| binding.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,606 @@
+use bevy::ecs::SystemParam;
+use bevy::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::level::{Cell, FilterMode, LevelDescription, MirrorKind, OrbMatch, RefactorKind};
+use crate::system_stages::screen_transformations::screen_to_coordinate;
+use crate::*;
+
+pub const NAME: &str = "editor";
+
+/// Where a level started from scratch is saved. It isn't part of any pack until it's added to one.
+pub const NEW_LEVEL_PATH: &str = "levels/untitled.lvl";
+
+const NEW_LEVEL_HEIGHT: usize = 9;
+const NEW_LEVEL_WIDTH: usize = 13;
+
+/// The cells picked with the number keys and then `-`, `G` and `=`, in order, with `G` and `=`
+/// matching the gate and plate tokens.
+const BRUSHES: [Cell; 13] = [
+    Cell::Empty,
+    Cell::Wall,
+    Cell::Crate,
+    Cell::Jimbo,
+    Cell::Orb {
+        orb_type: LaserType::RED,
+        rule: OrbMatch::Exact,
+    },
+    Cell::LaserSource {
+        laser_type: LaserType::RED,
+        direction: crate::Direction::Right,
+    },
+    Cell::Refactor(RefactorKind::Corner(crate::Direction::Up)),
+    Cell::Refactor(RefactorKind::Split(crate::Direction::Up)),
+    Cell::Refactor(RefactorKind::Cross),
+    Cell::Mirror {
+        kind: MirrorKind::Slash,
+        fixed: false,
+    },
+    Cell::Filter {
+        laser_type: LaserType::RED,
+        mode: FilterMode::Pass,
+        fixed: false,
+    },
+    Cell::Gate,
+    Cell::Plate(None),
+];
+
+const BRUSH_KEYS: [KeyCode; 13] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+    KeyCode::Minus,
+    KeyCode::G,
+    KeyCode::Equals,
+];
+
+pub fn stage() -> SystemStage {
+    let mut stage = SystemStage::parallel();
+    stage.add_system(edit_level.system());
+    stage.add_system(update_editor_hud.system());
+    stage.add_system(update_editor_links.system());
+    stage
+}
+
+/// The level being edited, which is respawned after every change so beams are traced live.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    pub path: PathBuf,
+    pub level: LevelDescription,
+    pub brush: Cell,
+    /// The outcome of the last save, shown under the controls.
+    pub status: String,
+    /// Set while a mouse button pressed over the level is held, so the click that opened the editor
+    /// doesn't paint.
+    painting: bool,
+    /// Whether clicks link cells instead of painting them.
+    link_mode: bool,
+    /// The gate or laser source that orbs and plates clicked in link mode are linked with.
+    link_from: Option<(usize, usize)>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor {
+            path: PathBuf::from(NEW_LEVEL_PATH),
+            level: LevelDescription {
+                width: NEW_LEVEL_WIDTH as u32,
+                height: NEW_LEVEL_HEIGHT as u32,
+                info: Default::default(),
+                rows: vec![vec![Cell::Empty; NEW_LEVEL_WIDTH]; NEW_LEVEL_HEIGHT],
+                links: Vec::new(),
+            },
+            brush: Cell::Wall,
+            status: String::new(),
+            painting: false,
+            link_mode: false,
+            link_from: None,
+        }
+    }
+}
+
+impl Editor {
+    pub fn open(path: PathBuf, level: LevelDescription) -> Self {
+        Editor {
+            path,
+            level,
+            ..Default::default()
+        }
+    }
+
+    /// Paints a cell, returning whether anything changed. A plate painted under something that
+    /// fits on one, or something painted onto a plate, ends up stacked on the plate.
+    fn paint(&mut self, coordinate: Coordinate, brush: Cell) -> bool {
+        let row = (self.level.height as i32 - 1 - coordinate.y) as usize;
+        let column = coordinate.x as usize;
+        let cell = match self.level.rows.get(row).and_then(|cells| cells.get(column)) {
+            Some(current) => match (current, brush) {
+                (current, Cell::Plate(None)) if current.fits_on_plate() => {
+                    Cell::Plate(Some(Box::new(current.clone())))
+                }
+                (Cell::Plate(_), brush) if brush.fits_on_plate() => {
+                    Cell::Plate(Some(Box::new(brush)))
+                }
+                (_, brush) => brush,
+            },
+            None => return false,
+        };
+        if self.level.rows[row][column] == cell {
+            return false;
+        }
+
+        // A level has exactly one Jimbo, so placing one moves it.
+        if cell.layers().contains(&&Cell::Jimbo) {
+            for current in self.level.rows.iter_mut().flatten() {
+                match current {
+                    Cell::Jimbo => *current = Cell::Empty,
+                    Cell::Plate(top) if top.as_deref() == Some(&Cell::Jimbo) => *top = None,
+                    _ => (),
+                }
+            }
+        }
+
+        self.level.rows[row][column] = cell;
+        self.level.prune_links();
+        true
+    }
+
+    /// The row and column under `coordinate`, if it's inside the level.
+    fn position(&self, coordinate: Coordinate) -> Option<(usize, usize)> {
+        let row = (self.level.height as i32 - 1 - coordinate.y) as usize;
+        let column = coordinate.x as usize;
+        self.level.rows.get(row)?.get(column)?;
+        Some((row, column))
+    }
+
+    /// Picks the gate or laser source to link from, or links or unlinks an orb or plate with the
+    /// one picked. Returns whether the links changed.
+    fn click_link(&mut self, coordinate: Coordinate) -> bool {
+        let position = match self.position(coordinate) {
+            Some(position) => position,
+            None => return false,
+        };
+        let layers = self.level.rows[position.0][position.1].layers();
+        let is_trigger = layers.iter().any(|layer| layer.is_trigger());
+        let is_switched = layers.iter().any(|layer| layer.is_switched());
+
+        match self.link_from {
+            Some(from) if from == position => self.link_from = None,
+            Some(from) if is_trigger => {
+                self.level.toggle_link(from, position);
+                return true;
+            }
+            _ if is_switched => self.link_from = Some(position),
+            _ => self.status = "Links start from a gate or laser source".to_string(),
+        }
+        false
+    }
+
+    /// Takes the plate out from under whatever is on it, returning whether there was a plate.
+    fn remove_plate(&mut self, coordinate: Coordinate) -> bool {
+        let (row, column) = match self.position(coordinate) {
+            Some(position) => position,
+            None => return false,
+        };
+        let cell = &mut self.level.rows[row][column];
+        *cell = match cell {
+            Cell::Plate(Some(top)) => (**top).clone(),
+            Cell::Plate(None) => Cell::Empty,
+            _ => return false,
+        };
+        self.level.prune_links();
+        true
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        let width = width.max(1);
+        let height = height.max(1);
+        for cells in self.level.rows.iter_mut() {
+            cells.resize(width, Cell::Empty);
+        }
+        self.level.rows.resize(height, vec![Cell::Empty; width]);
+        self.level.width = width as u32;
+        self.level.height = height as u32;
+        self.level.prune_links();
+        self.link_from = None;
+    }
+
+    fn save(&mut self) {
+        let jimbos = self
+            .level
+            .rows
+            .iter()
+            .flatten()
+            .flat_map(Cell::layers)
+            .filter(|layer| **layer == Cell::Jimbo)
+            .count();
+        if jimbos != 1 {
+            self.status = "Not saved: the level needs Jimbo (4)".to_string();
+            return;
+        }
+
+        let lvl = self.level.to_lvl_string();
+        if LevelDescription::parse(&lvl).as_ref() != Ok(&self.level) {
+            self.status = "Not saved: the level doesn't survive being written out".to_string();
+            return;
+        }
+
+        self.status = match fs::write(&self.path, lvl) {
+            Ok(()) => format!("Saved {}", self.path.display()),
+            Err(err) => format!("Failed to save {}: {}", self.path.display(), err),
+        };
+    }
+}
+
+/// Marks the editor text showing the brush and save status.
+#[derive(Debug, Copy, Clone)]
+pub struct EditorStatus;
+
+/// Marks the editor text listing the level's links.
+#[derive(Debug, Copy, Clone)]
+pub struct EditorLinks;
+
+pub fn spawn_editor(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    editor: &Editor,
+    level_resources: &mut map::LevelResources,
+) {
+    map::spawn_level(&editor.level, commands, level_resources);
+
+    let text = |value: String| Text {
+        value,
+        font: asset_server.load("fonts/Helvetica.ttf"),
+        style: TextStyle {
+            font_size: 18.0,
+            color: Color::WHITE,
+            alignment: TextAlignment {
+                horizontal: HorizontalAlign::Left,
+                vertical: VerticalAlign::Center,
+            },
+        },
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with(UiObject)
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: text(
+                    "0-9, -, G and = brush, D direction, C color, M mode, F fix in place, L link, arrows resize, Ctrl+S save, Esc menu"
+                        .to_string(),
+                ),
+                ..Default::default()
+            });
+            parent.spawn(TextBundle {
+                text: text(
+                    "Left click paints, right click erases, Shift+right click removes just a plate"
+                        .to_string(),
+                ),
+                ..Default::default()
+            });
+            parent
+                .spawn(TextBundle {
+                    text: text(status_text(editor)),
+                    ..Default::default()
+                })
+                .with(EditorStatus);
+            parent
+                .spawn(TextBundle {
+                    text: text(links_text(editor)),
+                    ..Default::default()
+                })
+                .with(EditorLinks);
+        });
+}
+
+/// The keys, mouse buttons and cursor the editor is worked with.
+#[derive(SystemParam)]
+pub struct EditorInput<'a> {
+    pub keyboard_input: Res<'a, Input<KeyCode>>,
+    pub mouse_input: Res<'a, Input<MouseButton>>,
+    pub windows: Res<'a, Windows>,
+}
+
+fn edit_level(
+    commands: &mut Commands,
+    state: Res<AppState>,
+    input: EditorInput,
+    mut editor: ResMut<Editor>,
+    mut level_resources: map::LevelResources,
+    level_objects: Query<Entity, With<LevelObject>>,
+) {
+    if *state != AppState::Editor {
+        return;
+    }
+
+    let EditorInput {
+        keyboard_input,
+        mouse_input,
+        windows,
+    } = input;
+    let ctrl =
+        keyboard_input.pressed(KeyCode::LControl) || keyboard_input.pressed(KeyCode::RControl);
+    if ctrl && keyboard_input.just_pressed(KeyCode::S) {
+        editor.save();
+        return;
+    }
+
+    for (key, brush) in BRUSH_KEYS.iter().zip(BRUSHES.iter()) {
+        if keyboard_input.just_pressed(*key) {
+            editor.brush = brush.clone();
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::D) {
+        editor.brush = rotated(&editor.brush);
+    }
+    if keyboard_input.just_pressed(KeyCode::C) {
+        editor.brush = recolored(&editor.brush);
+    }
+    if keyboard_input.just_pressed(KeyCode::F) {
+        editor.brush = fixed_toggled(&editor.brush);
+    }
+    if keyboard_input.just_pressed(KeyCode::M) {
+        editor.brush = mode_cycled(&editor.brush);
+    }
+    if keyboard_input.just_pressed(KeyCode::L) {
+        editor.link_mode = !editor.link_mode;
+        editor.link_from = None;
+    }
+
+    let (width, height) = (editor.level.width as usize, editor.level.height as usize);
+    let resized = if keyboard_input.just_pressed(KeyCode::Right) {
+        Some((width + 1, height))
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        Some((width - 1, height))
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        Some((width, height + 1))
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        Some((width, height - 1))
+    } else {
+        None
+    };
+
+    let mut changed = false;
+    if let Some((width, height)) = resized {
+        editor.resize(width, height);
+        changed = true;
+    }
+
+    if mouse_input.just_pressed(MouseButton::Left) || mouse_input.just_pressed(MouseButton::Right) {
+        editor.painting = true;
+    } else if !mouse_input.pressed(MouseButton::Left) && !mouse_input.pressed(MouseButton::Right) {
+        editor.painting = false;
+    }
+
+    let shift = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+    let window = windows.get_primary().unwrap();
+    let cursor = window
+        .cursor_position()
+        .and_then(|position| screen_to_coordinate(position, window, &level_resources.level_size));
+    if editor.link_mode {
+        if mouse_input.just_pressed(MouseButton::Right) {
+            editor.link_from = None;
+        } else if let (true, Some(coordinate)) =
+            (mouse_input.just_pressed(MouseButton::Left), cursor)
+        {
+            changed |= editor.click_link(coordinate);
+        }
+    }
+
+    let painted = if !editor.painting || editor.link_mode {
+        None
+    } else if mouse_input.pressed(MouseButton::Left) {
+        Some(editor.brush.clone())
+    } else if mouse_input.pressed(MouseButton::Right) {
+        Some(Cell::Empty)
+    } else {
+        None
+    };
+    if let (Some(cell), Some(coordinate)) = (painted, cursor) {
+        changed |= if shift && cell == Cell::Empty {
+            editor.remove_plate(coordinate)
+        } else {
+            editor.paint(coordinate, cell)
+        };
+    }
+
+    if !changed {
+        return;
+    }
+
+    editor.status.clear();
+    for ent in level_objects.iter() {
+        commands.despawn_recursive(ent);
+    }
+    map::spawn_level(&editor.level, commands, &mut level_resources);
+}
+
+fn update_editor_hud(
+    state: Res<AppState>,
+    editor: Res<Editor>,
+    mut q: Query<&mut Text, With<EditorStatus>>,
+) {
+    if *state != AppState::Editor {
+        return;
+    }
+
+    let status = status_text(&editor);
+    for mut text in q.iter_mut() {
+        if text.value != status {
+            text.value = status.clone();
+        }
+    }
+}
+
+fn update_editor_links(
+    state: Res<AppState>,
+    editor: Res<Editor>,
+    mut q: Query<&mut Text, With<EditorLinks>>,
+) {
+    if *state != AppState::Editor {
+        return;
+    }
+
+    let links = links_text(&editor);
+    for mut text in q.iter_mut() {
+        if text.value != links {
+            text.value = links.clone();
+        }
+    }
+}
+
+fn status_text(editor: &Editor) -> String {
+    let tool = match (editor.link_mode, editor.link_from) {
+        (false, _) => format!("Brush: {}", editor.brush.token()),
+        (true, None) => "Linking: click a gate or laser source".to_string(),
+        (true, Some((row, column))) => format!(
+            "Linking {},{}: click orbs and plates to link or unlink them, right click to stop",
+            row + 1,
+            column + 1
+        ),
+    };
+    format!(
+        "{} ({}x{})  {}  {}",
+        editor.path.display(),
+        editor.level.width,
+        editor.level.height,
+        tool,
+        editor.status
+    )
+}
+
+/// The links as they'll be saved, in `group: row,column ...` form.
+fn links_text(editor: &Editor) -> String {
+    if editor.level.links.is_empty() {
+        return "Links: none".to_string();
+    }
+    let links = editor
+        .level
+        .links
+        .iter()
+        .map(|link| link.to_string())
+        .collect::<Vec<_>>()
+        .join("   ");
+    format!("Links: {}", links)
+}
+
+fn rotated(cell: &Cell) -> Cell {
+    match cell.clone() {
+        Cell::LaserSource {
+            laser_type,
+            direction,
+        } => Cell::LaserSource {
+            laser_type,
+            direction: direction.rotated_90(),
+        },
+        Cell::Refactor(RefactorKind::Corner(direction)) => {
+            Cell::Refactor(RefactorKind::Corner(direction.rotated_90()))
+        }
+        Cell::Refactor(RefactorKind::Split(direction)) => {
+            Cell::Refactor(RefactorKind::Split(direction.rotated_90()))
+        }
+        Cell::Mirror { kind, fixed } => Cell::Mirror {
+            kind: kind.flipped(),
+            fixed,
+        },
+        cell => cell,
+    }
+}
+
+fn fixed_toggled(cell: &Cell) -> Cell {
+    match cell.clone() {
+        Cell::Mirror { kind, fixed } => Cell::Mirror {
+            kind,
+            fixed: !fixed,
+        },
+        Cell::Filter {
+            laser_type,
+            mode,
+            fixed,
+        } => Cell::Filter {
+            laser_type,
+            mode,
+            fixed: !fixed,
+        },
+        cell => cell,
+    }
+}
+
+/// Cycles through how orbs match beams and what filters do to them.
+fn mode_cycled(cell: &Cell) -> Cell {
+    match cell.clone() {
+        Cell::Orb { orb_type, rule } => Cell::Orb {
+            orb_type,
+            rule: rule.next(),
+        },
+        Cell::Filter {
+            laser_type,
+            mode,
+            fixed,
+        } => Cell::Filter {
+            laser_type,
+            mode: mode.next(),
+            fixed,
+        },
+        cell => cell,
+    }
+}
+
+/// Cycles through the colors for the cell.
+fn recolored(cell: &Cell) -> Cell {
+    let next = |laser_type: LaserType| {
+        let index = LaserType::ALL
+            .iter()
+            .position(|candidate| *candidate == laser_type)
+            .unwrap_or(0);
+        LaserType::ALL[(index + 1) % LaserType::ALL.len()]
+    };
+
+    match cell.clone() {
+        Cell::Orb { orb_type, rule } => Cell::Orb {
+            orb_type: next(orb_type),
+            rule,
+        },
+        Cell::LaserSource {
+            laser_type,
+            direction,
+        } => Cell::LaserSource {
+            laser_type: next(laser_type),
+            direction,
+        },
+        Cell::Filter {
+            laser_type,
+            mode,
+            fixed,
+        } => Cell::Filter {
+            laser_type: next(laser_type),
+            mode,
+            fixed,
+        },
+        cell => cell,
+    }
+}
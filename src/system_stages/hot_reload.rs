@@ -0,0 +1,152 @@
+use bevy::ecs::SystemParam;
+use bevy::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::level::LevelDescription;
+use crate::system_stages::hud;
+use crate::*;
+
+pub const NAME: &str = "hot-reload";
+
+/// How often the open level file is checked for changes.
+const POLL_SECONDS: f32 = 0.5;
+
+pub fn stage() -> SystemStage {
+    let mut stage = SystemStage::parallel();
+    stage.add_system(reload_changed_level.system());
+    stage
+}
+
+/// The level file being watched and when it was last modified.
+#[derive(Debug, Clone, Default)]
+pub struct LevelWatcher {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    countdown: f32,
+}
+
+/// Marks the message shown when the watched level fails to reload.
+#[derive(Debug, Copy, Clone)]
+pub struct ReloadError;
+
+/// What a reload replaces: the level's objects and the error left by the last failed reload.
+#[derive(SystemParam)]
+pub struct ReloadedObjects<'a> {
+    pub level_objects: Query<'a, Entity, With<LevelObject>>,
+    pub reload_errors: Query<'a, Entity, With<ReloadError>>,
+}
+
+fn reload_changed_level(
+    commands: &mut Commands,
+    state: Res<AppState>,
+    time: Res<Time>,
+    mut watcher: ResMut<LevelWatcher>,
+    mut level_resources: map::LevelResources,
+    hud_resources: hud::HudResources,
+    reloaded: ReloadedObjects,
+) {
+    let level_index = match *state {
+        AppState::Level(level_index) => level_index,
+        _ => return,
+    };
+
+    watcher.countdown -= time.delta_seconds();
+    if watcher.countdown > 0.0 {
+        return;
+    }
+    watcher.countdown = POLL_SECONDS;
+
+    let path = level_resources.recorder.0.level.clone();
+    let modified = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    if watcher.path != path {
+        *watcher = LevelWatcher {
+            path,
+            modified,
+            countdown: POLL_SECONDS,
+        };
+        return;
+    }
+    if watcher.modified == modified {
+        return;
+    }
+    watcher.modified = modified;
+
+    for ent in reloaded.reload_errors.iter() {
+        commands.despawn_recursive(ent);
+    }
+
+    // Check the file parses before despawning anything, so a half-written level leaves the last
+    // good one playable.
+    let result = LevelDescription::load(&path).and_then(|_| {
+        for ent in reloaded.level_objects.iter() {
+            commands.despawn_recursive(ent);
+        }
+        map::try_load_level(&path, commands, &mut level_resources)
+    });
+
+    let hud::HudResources {
+        asset_server,
+        mut materials,
+        packs,
+        progress,
+    } = hud_resources;
+    match result {
+        Ok(description) => hud::spawn_hud(
+            commands,
+            &asset_server,
+            &mut materials,
+            &hud::level_title(&packs, level_index),
+            &description.info,
+            progress.best_moves(&path),
+        ),
+        Err(err) => {
+            eprintln!("{}", err);
+            spawn_reload_error(commands, &asset_server, &mut materials, &err);
+        }
+    }
+}
+
+fn spawn_reload_error(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    error: &map::LevelParseError,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.6).into()),
+            ..Default::default()
+        })
+        .with(UiObject)
+        .with(ReloadError)
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text {
+                    value: error.to_string(),
+                    font: asset_server.load("fonts/Helvetica.ttf"),
+                    style: TextStyle {
+                        font_size: 20.0,
+                        color: Color::rgb(0.9, 0.3, 0.3),
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Left,
+                            vertical: VerticalAlign::Center,
+                        },
+                    },
+                },
+                ..Default::default()
+            });
+        });
+}
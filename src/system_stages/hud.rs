@@ -0,0 +1,342 @@
+use bevy::ecs::{Component, SystemParam};
+use bevy::prelude::*;
+
+use crate::level::LevelInfo;
+use crate::*;
+
+pub const NAME: &str = "hud";
+
+pub fn stage() -> SystemStage {
+    let mut stage = SystemStage::parallel();
+    stage.add_system(update_moves.system());
+    stage.add_system(show_level_completed.system());
+    stage
+}
+
+/// Marks the HUD text showing the move count.
+#[derive(Debug, Copy, Clone)]
+pub struct HudMoves;
+
+/// The name a level is shown under, falling back to its position when it has no title.
+pub fn level_title(packs: &LevelPacks, level_index: usize) -> String {
+    packs
+        .level(level_index)
+        .and_then(|level| level.title.clone())
+        .unwrap_or_else(|| format!("Level {}", level_index + 1))
+}
+
+pub fn spawn_hud(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    title: &str,
+    info: &LevelInfo,
+    best: Option<usize>,
+) {
+    let font = asset_server.load("fonts/Helvetica.ttf");
+    let text = |value: String, font_size: f32| Text {
+        value,
+        font: font.clone(),
+        style: TextStyle {
+            font_size,
+            color: Color::WHITE,
+            alignment: TextAlignment {
+                horizontal: HorizontalAlign::Left,
+                vertical: VerticalAlign::Center,
+            },
+        },
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with(LevelObject)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: Rect {
+                            bottom: Val::Px(10.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::NONE.into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    spawn_hud_button(
+                        parent,
+                        materials,
+                        text("Restart".to_string(), 18.0),
+                        LevelCommand::Restart,
+                    );
+                    spawn_hud_button(
+                        parent,
+                        materials,
+                        text("Menu".to_string(), 18.0),
+                        AppStateChangeEvent(AppState::LevelSelect),
+                    );
+                });
+
+            parent.spawn(TextBundle {
+                text: text(title.to_string(), 25.0),
+                ..Default::default()
+            });
+
+            parent
+                .spawn(TextBundle {
+                    text: text(moves_text(0, info.par), 20.0),
+                    ..Default::default()
+                })
+                .with(HudMoves);
+
+            if let Some(best) = best {
+                parent.spawn(TextBundle {
+                    text: text(format!("Best: {}", best), 20.0),
+                    ..Default::default()
+                });
+            }
+
+            if let Some(difficulty) = &info.difficulty {
+                parent.spawn(TextBundle {
+                    text: text(format!("Difficulty: {}", difficulty), 20.0),
+                    ..Default::default()
+                });
+            }
+
+            if let Some(hint) = &info.hint {
+                parent.spawn(TextBundle {
+                    text: text(format!("Hint: {}", hint), 20.0),
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+fn spawn_hud_button(
+    parent: &mut ChildBuilder,
+    materials: &mut Assets<ColorMaterial>,
+    label: Text,
+    action: impl Component,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                size: bevy::prelude::Size::new(Val::Px(90.0), Val::Px(35.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                margin: Rect {
+                    right: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: materials.add(Color::rgb(0.6, 0.2, 0.2).into()),
+            ..Default::default()
+        })
+        .with(action)
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: label,
+                ..Default::default()
+            });
+        });
+}
+
+fn update_moves(
+    state: Res<AppState>,
+    turn_counter: Res<TurnCounter>,
+    level_info: Res<LevelInfo>,
+    mut q: Query<&mut Text, With<HudMoves>>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
+
+    let moves = moves_text(turn_counter.0, level_info.par);
+    for mut text in q.iter_mut() {
+        if text.value != moves {
+            text.value = moves.clone();
+        }
+    }
+}
+
+/// The resources the HUD and the level-complete overlay are spawned from.
+#[derive(SystemParam)]
+pub struct HudResources<'a> {
+    pub asset_server: Res<'a, AssetServer>,
+    pub materials: ResMut<'a, Assets<ColorMaterial>>,
+    pub packs: Res<'a, LevelPacks>,
+    pub progress: Res<'a, Progress>,
+}
+
+fn show_level_completed(
+    commands: &mut Commands,
+    mut event_reader: Local<EventReader<LevelCompletedEvent>>,
+    events: Res<Events<LevelCompletedEvent>>,
+    mut hud_resources: HudResources,
+    level_info: Res<LevelInfo>,
+) {
+    if let Some(completed) = event_reader.latest(&events) {
+        let best = hud_resources
+            .packs
+            .level(completed.level)
+            .and_then(|level| hud_resources.progress.best_moves(&level.file));
+        spawn_level_completed(
+            commands,
+            &hud_resources.asset_server,
+            &mut hud_resources.materials,
+            &hud_resources.packs,
+            &level_info,
+            completed,
+            best,
+        );
+    }
+}
+
+pub fn spawn_level_completed(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    packs: &LevelPacks,
+    info: &LevelInfo,
+    completed: &LevelCompletedEvent,
+    best: Option<usize>,
+) {
+    let font = asset_server.load("fonts/Helvetica.ttf");
+    let text = |value: String, font_size: f32| Text {
+        value,
+        font: font.clone(),
+        style: TextStyle {
+            font_size,
+            color: Color::WHITE,
+            alignment: TextAlignment {
+                horizontal: HorizontalAlign::Center,
+                vertical: VerticalAlign::Center,
+            },
+        },
+    };
+
+    let title = level_title(packs, completed.level);
+    let verdict = match info.par {
+        Some(par) if completed.moves < par => "Under par!",
+        Some(par) if completed.moves == par => "Right on par.",
+        Some(_) => "Can you make it to par?",
+        None => "",
+    };
+
+    let mut buttons = Vec::new();
+    if completed.level + 1 < packs.len() {
+        buttons.push(("Next", AppState::Level(completed.level + 1)));
+    }
+    buttons.push(("Retry", AppState::Level(completed.level)));
+    buttons.push(("Menu", AppState::LevelSelect));
+
+    let button_material = materials.add(Color::rgb(0.6, 0.2, 0.2).into());
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: bevy::prelude::Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.6).into()),
+            ..Default::default()
+        })
+        .with(UiObject)
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                text: text("Level Complete".to_string(), 30.0),
+                ..Default::default()
+            });
+            parent.spawn(TextBundle {
+                text: text(title, 25.0),
+                ..Default::default()
+            });
+            parent.spawn(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                text: text(moves_text(completed.moves, info.par), 20.0),
+                ..Default::default()
+            });
+            if let Some(best) = best {
+                parent.spawn(TextBundle {
+                    text: text(format!("Best: {}", best), 20.0),
+                    ..Default::default()
+                });
+            }
+            if !verdict.is_empty() {
+                parent.spawn(TextBundle {
+                    text: text(verdict.to_string(), 20.0),
+                    ..Default::default()
+                });
+            }
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::NONE.into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (label, next_state) in buttons {
+                        parent
+                            .spawn(ButtonBundle {
+                                style: Style {
+                                    size: bevy::prelude::Size::new(Val::Px(120.0), Val::Px(50.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    margin: Rect::all(Val::Px(10.0)),
+                                    ..Default::default()
+                                },
+                                material: button_material.clone(),
+                                ..Default::default()
+                            })
+                            .with(AppStateChangeEvent(next_state))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle {
+                                    text: text(label.to_string(), 20.0),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+        });
+}
+
+fn moves_text(moves: usize, par: Option<usize>) -> String {
+    match par {
+        Some(par) => format!("Moves: {} / Par: {}", moves, par),
+        None => format!("Moves: {}", moves),
+    }
+}
@@ -1,150 +1,393 @@
+use bevy::ecs::SystemParam;
 use bevy::prelude::*;
+use std::fs;
 
+use crate::gamepad::{GamepadAction, GamepadInput};
+use crate::key_bindings::{Action, LEVEL_HOTKEYS};
+use crate::replay::{Playback, Replay};
+use crate::system_stages::controls::{self, Rebinding};
+use crate::system_stages::editor::{self, Editor};
+use crate::system_stages::hud;
+use crate::system_stages::walk::Walk;
 use crate::*;
 
 pub const NAME: &str = "input";
 
 pub fn stage() -> SystemStage {
     let mut system = SystemStage::parallel();
-    system.add_system(jimbo_movement.system());
-    system.add_system(undo.system());
+    system.add_system(level_input.system());
+    system.add_system(gamepad_input.system());
+    system.add_system(replay_keys.system());
+    system.add_system(lurd_keys.system());
+    system.add_system(play_replay.system());
     system.add_system(detect_level_change.system());
     system.add_system(detect_level_select.system());
     system.add_system(app_state_change_event.system());
     system
 }
 
-fn jimbo_movement(
+/// The level commands that have keys of their own.
+const LEVEL_ACTIONS: [(Action, LevelCommand); 7] = [
+    (Action::MoveLeft, LevelCommand::Move(crate::Direction::Left)),
+    (
+        Action::MoveRight,
+        LevelCommand::Move(crate::Direction::Right),
+    ),
+    (Action::MoveDown, LevelCommand::Move(crate::Direction::Down)),
+    (Action::MoveUp, LevelCommand::Move(crate::Direction::Up)),
+    (Action::Undo, LevelCommand::Undo),
+    (Action::Redo, LevelCommand::Redo),
+    (Action::Restart, LevelCommand::Restart),
+];
+
+/// Turns level keys and clicks on the HUD buttons into level commands.
+fn level_input(
     state: Res<AppState>,
     keyboard_input: Res<Input<KeyCode>>,
-    tracker: Res<EntityTracker>,
-    level_size: Res<LevelSize>,
-    materials: Res<Materials>,
-    mut turn_counter: ResMut<TurnCounter>,
-    mut undo_buffer: ResMut<UndoBuffer>,
-    mut q: QuerySet<(
-        Query<(Entity, &Coordinate, &mut Handle<ColorMaterial>), With<Jimbo>>,
-        Query<&mut Coordinate>,
-        Query<&Movable>,
-    )>,
+    bindings: Res<KeyBindings>,
+    playback: Res<Playback>,
+    mut level_commands: ResMut<Events<LevelCommand>>,
+    interaction_q: Query<(&Interaction, &LevelCommand), Changed<Interaction>>,
 ) {
     match *state {
         AppState::Level(_) => (),
         _ => return,
     }
 
-    let (jimbo, coordinate, mut material) = q
-        .q0_mut()
-        .iter_mut()
-        .next()
-        .expect("Should always have jimbo");
-
-    let (direction, new_material) = if keyboard_input.just_pressed(KeyCode::Left) {
-        (IVec2::new(-1, 0), materials.jimbo_left.clone())
-    } else if keyboard_input.just_pressed(KeyCode::Right) {
-        (IVec2::new(1, 0), materials.jimbo_right.clone())
-    } else if keyboard_input.just_pressed(KeyCode::Down) {
-        (IVec2::new(0, -1), materials.jimbo_down.clone())
-    } else if keyboard_input.just_pressed(KeyCode::Up) {
-        (IVec2::new(0, 1), materials.jimbo_up.clone())
-    } else {
+    // Playback takes the place of the player until it's done.
+    if playback.is_playing() {
         return;
-    };
-    turn_counter.0 += 1;
-    *material = new_material;
-
-    let mut check_coordinate = *coordinate + direction;
-    let mut move_entities = vec![jimbo];
-    'outer: while let Some(entities) = tracker.0.get(&check_coordinate) {
-        let mut has_movable = false;
-        for ent in entities {
-            if let Ok(movable) = q.q2().get(*ent) {
-                if !movable.0 {
-                    move_entities.clear();
-                    break 'outer;
-                }
-                has_movable = true;
-                move_entities.push(*ent);
-            }
+    }
+
+    for (action, command) in LEVEL_ACTIONS.iter() {
+        if bindings.just_pressed(*action, &keyboard_input) {
+            level_commands.send(*command);
         }
-        if !has_movable {
-            break 'outer;
+    }
+
+    for (interaction, command) in interaction_q.iter() {
+        if *interaction == Interaction::Clicked {
+            level_commands.send(*command);
         }
-        check_coordinate += direction;
     }
+}
 
-    if check_coordinate.x < 0
-        || check_coordinate.x >= level_size.width as i32
-        || check_coordinate.y < 0
-        || check_coordinate.y >= level_size.height as i32
-    {
-        move_entities.clear();
+/// The button picked with the gamepad on menu screens, drawn in a highlight color while focused.
+#[derive(Debug, Clone, Default)]
+pub struct GamepadFocus {
+    entity: Option<Entity>,
+    /// The focused button's own material, put back when focus moves on.
+    material: Handle<ColorMaterial>,
+}
+
+/// Gamepad events and the state that turns them into actions.
+#[derive(SystemParam)]
+pub struct GamepadActions<'a> {
+    pub event_reader: Local<'a, EventReader<GamepadEvent>>,
+    pub events: Res<'a, Events<GamepadEvent>>,
+    pub gamepad: ResMut<'a, GamepadInput>,
+}
+
+impl GamepadActions<'_> {
+    fn read(&mut self) -> Vec<GamepadAction> {
+        let gamepad = &mut self.gamepad;
+        self.event_reader
+            .iter(&self.events)
+            .filter_map(|event| gamepad.handle(event))
+            .collect()
+    }
+}
+
+/// The menu buttons the gamepad moves between, and the one it's on.
+#[derive(SystemParam)]
+pub struct MenuFocus<'a> {
+    pub materials: Res<'a, Materials>,
+    pub focus: ResMut<'a, GamepadFocus>,
+    pub button_q: Query<
+        'a,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static AppStateChangeEvent,
+            &'static mut Handle<ColorMaterial>,
+        ),
+    >,
+}
+
+/// Plays levels with the gamepad, and moves between the buttons on the other screens with it.
+fn gamepad_input(
+    state: Res<AppState>,
+    mut gamepad_actions: GamepadActions,
+    playback: Res<Playback>,
+    mut walk: ResMut<Walk>,
+    menu_focus: MenuFocus,
+    mut level_commands: ResMut<Events<LevelCommand>>,
+    mut state_changes: ResMut<Events<AppStateChangeEvent>>,
+) {
+    let MenuFocus {
+        materials,
+        mut focus,
+        mut button_q,
+    } = menu_focus;
+
+    // The focused button goes away with the screen it was on.
+    if let Some(entity) = focus.entity {
+        if button_q
+            .get_component::<AppStateChangeEvent>(entity)
+            .is_err()
+        {
+            focus.entity = None;
+        }
     }
 
-    for ent in move_entities.into_iter() {
-        let undo = Box::new(move |world: &mut World| {
-            if let Ok(mut coordinate) = world.get_mut::<Coordinate>(ent) {
-                *coordinate -= direction;
+    for action in gamepad_actions.read() {
+        let level_command = match (*state, action) {
+            (_, GamepadAction::Menu) => {
+                if *state != AppState::LevelSelect {
+                    state_changes.send(AppStateChangeEvent(AppState::LevelSelect));
+                }
+                continue;
+            }
+            (AppState::Level(_), GamepadAction::Move(direction)) => LevelCommand::Move(direction),
+            (AppState::Level(_), GamepadAction::Undo) => LevelCommand::Undo,
+            (AppState::Level(_), GamepadAction::Redo) => LevelCommand::Redo,
+            (AppState::Level(_), GamepadAction::Restart) => LevelCommand::Restart,
+            (AppState::Level(_), _) | (AppState::Editor, _) => continue,
+            (_, GamepadAction::Move(direction)) => {
+                let from = focus
+                    .entity
+                    .and_then(|entity| button_q.get_component::<GlobalTransform>(entity).ok())
+                    .map(|transform| transform.translation.truncate());
+                let buttons = button_q
+                    .iter_mut()
+                    .map(|(entity, transform, _, _)| (entity, transform.translation.truncate()));
+                if let Some(next) = next_focus(from, direction, buttons) {
+                    move_focus(&mut focus, next, &materials, &mut button_q);
+                }
+                continue;
             }
-        });
+            (_, GamepadAction::Confirm) => {
+                let focused = focus
+                    .entity
+                    .and_then(|entity| button_q.get_component::<AppStateChangeEvent>(entity).ok());
+                if let Some(state_change) = focused {
+                    state_changes.send(*state_change);
+                }
+                continue;
+            }
+            _ => continue,
+        };
 
-        undo_buffer.0.push((turn_counter.0, undo));
-        let mut coordinate = q
-            .q1_mut()
-            .get_mut(ent)
-            .expect("This entity should have a coordinate");
-        *coordinate += direction;
+        // Playback takes the place of the player until it's done, while a walk gives way to them.
+        if !playback.is_playing() {
+            walk.0.stop();
+            level_commands.send(level_command);
+        }
     }
 }
 
-fn undo(world: &mut World, resources: &mut Resources) {
-    let state = resources
-        .get::<AppState>()
-        .expect("App should always have a state");
+/// The nearest button in the given direction, or the top left one when nothing is focused yet.
+/// Buttons off to the side count for more than ones straight ahead so rows and columns are kept to.
+fn next_focus(
+    from: Option<Vec2>,
+    direction: crate::Direction,
+    buttons: impl Iterator<Item = (Entity, Vec2)>,
+) -> Option<Entity> {
+    let from = match from {
+        Some(from) => from,
+        None => {
+            return buttons
+                .min_by(|(_, a), (_, b)| {
+                    b.y.partial_cmp(&a.y)
+                        .unwrap()
+                        .then(a.x.partial_cmp(&b.x).unwrap())
+                })
+                .map(|(entity, _)| entity)
+        }
+    };
+
+    let step = direction.direction();
+    let step = Vec2::new(step.x as f32, step.y as f32);
+    buttons
+        .filter_map(|(entity, position)| {
+            let offset = position - from;
+            let along = offset.dot(step);
+            let across = (offset - step * along).length();
+            if along < 1.0 {
+                None
+            } else {
+                Some((entity, along + across * 2.0))
+            }
+        })
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(entity, _)| entity)
+}
+
+fn move_focus(
+    focus: &mut GamepadFocus,
+    next: Entity,
+    materials: &Materials,
+    button_q: &mut Query<(
+        Entity,
+        &GlobalTransform,
+        &AppStateChangeEvent,
+        &mut Handle<ColorMaterial>,
+    )>,
+) {
+    if let Some(previous) = focus.entity {
+        if let Ok((_, _, _, mut material)) = button_q.get_mut(previous) {
+            *material = focus.material.clone();
+        }
+    }
 
+    if let Ok((_, _, _, mut material)) = button_q.get_mut(next) {
+        focus.material = material.clone();
+        *material = materials.button_focused.clone();
+        focus.entity = Some(next);
+    }
+}
+
+fn replay_keys(
+    state: Res<AppState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut level_resources: map::LevelResources,
+) {
     match *state {
         AppState::Level(_) => (),
         _ => return,
     }
 
-    let input = resources
-        .get::<Input<KeyCode>>()
-        .expect("Input resource should have been available");
-    if !input.just_pressed(KeyCode::Z) {
-        return;
+    let recording = &level_resources.recorder.0;
+    if bindings.just_pressed(Action::SaveReplay, &keyboard_input) {
+        match Replay::default_path(&recording.level, recording.level_hash) {
+            Some(path) => match recording.save(&path) {
+                Ok(()) => println!("Saved replay to {}", path.display()),
+                Err(err) => eprintln!("{}", err),
+            },
+            None => eprintln!("Couldn't find a data directory to save the replay in"),
+        }
+    } else if bindings.just_pressed(Action::PlayReplay, &keyboard_input) {
+        let path = match Replay::default_path(&recording.level, recording.level_hash) {
+            Some(path) => path,
+            None => return,
+        };
+        let replay = match Replay::load(&path) {
+            Ok(replay) => replay,
+            Err(err) => {
+                eprintln!("{}", err);
+                return;
+            }
+        };
+        if replay.level_hash != recording.level_hash {
+            eprintln!(
+                "{}: the level has changed since this replay was recorded",
+                path.display()
+            );
+            return;
+        }
+
+        map::reset_level(&mut level_resources);
+        level_resources.playback.play(replay.commands);
+    } else if bindings.just_pressed(Action::SlowerPlayback, &keyboard_input) {
+        let playback = &mut level_resources.playback;
+        playback.moves_per_second = (playback.moves_per_second / 2.0).max(0.5);
+    } else if bindings.just_pressed(Action::FasterPlayback, &keyboard_input) {
+        let playback = &mut level_resources.playback;
+        playback.moves_per_second = (playback.moves_per_second * 2.0).min(64.0);
     }
+}
 
-    let mut current_turn = resources
-        .get_mut::<TurnCounter>()
-        .expect("Should've had the current turn");
+fn lurd_keys(
+    state: Res<AppState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut level_resources: map::LevelResources,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
 
-    if current_turn.0 == 0 {
+    let export = bindings.just_pressed(Action::ExportMoves, &keyboard_input);
+    if !export && !bindings.just_pressed(Action::ImportMoves, &keyboard_input) {
         return;
     }
 
-    let mut undo_buffer = resources
-        .get_mut::<UndoBuffer>()
-        .expect("UndoBuffer should've been available");
+    let recording = &level_resources.recorder.0;
+    let path = match lurd::default_path(&recording.level, recording.level_hash) {
+        Some(path) => path,
+        None => {
+            eprintln!("Couldn't find a data directory to keep moves in");
+            return;
+        }
+    };
 
-    while let Some(undo) = undo_buffer.0.last() {
-        if undo.0 == current_turn.0 {
-            let func = undo_buffer.0.pop().unwrap().1;
-            func(world);
-        } else {
-            break;
+    if export {
+        let moves = level_resources.move_log.moves();
+        println!("{}", moves);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, format!("{}\n", moves)));
+        match written {
+            Ok(()) => println!("Saved moves to {}", path.display()),
+            Err(err) => eprintln!("{}: failed to write moves: {}", path.display(), err),
         }
+    } else {
+        let moves = match fs::read_to_string(&path) {
+            Ok(moves) => moves,
+            Err(err) => {
+                eprintln!("{}: failed to read moves: {}", path.display(), err);
+                return;
+            }
+        };
+        let directions = match lurd::parse(&moves) {
+            Ok(directions) => directions,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                return;
+            }
+        };
+
+        map::reset_level(&mut level_resources);
+        level_resources
+            .playback
+            .play(directions.into_iter().map(LevelCommand::Move));
     }
+}
 
-    current_turn.0 -= 1;
+fn play_replay(
+    state: Res<AppState>,
+    time: Res<Time>,
+    mut playback: ResMut<Playback>,
+    mut level_commands: ResMut<Events<LevelCommand>>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => {
+            playback.stop();
+            return;
+        }
+    }
+
+    if let Some(command) = playback.tick(time.delta_seconds()) {
+        level_commands.send(command);
+    }
 }
 
 fn detect_level_select(
     state: Res<AppState>,
     mut my_events: ResMut<Events<AppStateChangeEvent>>,
-    interaction_q: Query<(&Interaction, &AppStateChangeEvent), With<Button>>,
+    interaction_q: Query<(&Interaction, &AppStateChangeEvent), Changed<Interaction>>,
 ) {
-    if *state != AppState::LevelSelect {
-        return;
+    // The HUD and the controls screen have a way back to the menu too.
+    match *state {
+        AppState::LevelSelect
+        | AppState::LevelCompleted(_)
+        | AppState::Level(_)
+        | AppState::Controls => (),
+        AppState::Editor => return,
     }
 
     for (interaction, level_change) in interaction_q.iter() {
@@ -163,37 +406,80 @@ fn detect_level_select(
 }
 
 fn detect_level_change(
+    state: Res<AppState>,
     keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    rebinding: Res<Rebinding>,
+    packs: Res<LevelPacks>,
     mut my_events: ResMut<Events<AppStateChangeEvent>>,
 ) {
-    let level = if keyboard_input.just_pressed(KeyCode::Key1) {
-        0
-    } else if keyboard_input.just_pressed(KeyCode::Key2) {
-        1
-    } else if keyboard_input.just_pressed(KeyCode::Key3) {
-        2
-    } else if keyboard_input.just_pressed(KeyCode::Key4) {
-        3
-    } else if keyboard_input.just_pressed(KeyCode::Key5) {
-        4
-    } else if keyboard_input.just_pressed(KeyCode::Key6) {
-        5
-    } else if keyboard_input.just_pressed(KeyCode::Key7) {
-        6
-    } else if keyboard_input.just_pressed(KeyCode::Key8) {
-        7
-    } else if keyboard_input.just_pressed(KeyCode::Key9) {
-        8
-    } else if keyboard_input.just_pressed(KeyCode::Key0) {
-        9
-    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+    // The key being captured is bound, not acted on.
+    if rebinding.waiting.is_some() {
+        return;
+    }
+
+    // The editor has keys of its own.
+    if *state == AppState::Editor && !bindings.just_pressed(Action::Menu, &keyboard_input) {
+        return;
+    }
+
+    match *state {
+        AppState::Level(_) if bindings.just_pressed(Action::Edit, &keyboard_input) => {
+            my_events.send(AppStateChangeEvent(AppState::Editor));
+            return;
+        }
+        AppState::LevelSelect if bindings.just_pressed(Action::Controls, &keyboard_input) => {
+            my_events.send(AppStateChangeEvent(AppState::Controls));
+            return;
+        }
+        _ => (),
+    }
+
+    let level = if let (AppState::LevelCompleted(level), true) = (
+        *state,
+        bindings.just_pressed(Action::NextLevel, &keyboard_input),
+    ) {
+        level + 1
+    } else if let Some(level) = (0..LEVEL_HOTKEYS)
+        .find(|level| bindings.just_pressed(Action::Level(*level), &keyboard_input))
+    {
+        level
+    } else if bindings.just_pressed(Action::Menu, &keyboard_input) {
         my_events.send(AppStateChangeEvent(AppState::LevelSelect));
         return;
     } else {
         return;
     };
 
-    my_events.send(AppStateChangeEvent(AppState::Level(level)));
+    if level < packs.len() {
+        my_events.send(AppStateChangeEvent(AppState::Level(level)));
+    }
+}
+
+/// The resources each screen is spawned from.
+#[derive(SystemParam)]
+pub struct ScreenResources<'a> {
+    pub asset_server: Res<'a, AssetServer>,
+    pub color_materials: ResMut<'a, Assets<ColorMaterial>>,
+    pub packs: Res<'a, LevelPacks>,
+    pub progress: ResMut<'a, Progress>,
+    pub editor: ResMut<'a, Editor>,
+    pub bindings: Res<'a, KeyBindings>,
+}
+
+/// Everything spawned for the screen being left.
+#[derive(SystemParam)]
+pub struct ScreenObjects<'a> {
+    pub ui_objects: Query<'a, Entity, With<UiObject>>,
+    pub level_objects: Query<'a, Entity, With<LevelObject>>,
+}
+
+impl ScreenObjects<'_> {
+    fn despawn(&self, commands: &mut Commands) {
+        for ent in self.ui_objects.iter().chain(self.level_objects.iter()) {
+            commands.despawn_recursive(ent);
+        }
+    }
 }
 
 fn app_state_change_event(
@@ -201,45 +487,98 @@ fn app_state_change_event(
     mut state: ResMut<AppState>,
     mut event_reader: Local<EventReader<AppStateChangeEvent>>,
     events: Res<Events<AppStateChangeEvent>>,
-    ui_objects: Query<Entity, With<UiObject>>,
-    level_objects: Query<Entity, With<LevelObject>>,
-    materials: Res<Materials>,
-    mut level_size: ResMut<LevelSize>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut turn_counter: ResMut<TurnCounter>,
-    mut undo_buffer: ResMut<UndoBuffer>,
-    asset_server: Res<AssetServer>,
-    color_materials: ResMut<Assets<ColorMaterial>>,
+    screen_objects: ScreenObjects,
+    mut level_resources: map::LevelResources,
+    screen_resources: ScreenResources,
 ) {
+    let ScreenResources {
+        asset_server,
+        mut color_materials,
+        packs,
+        mut progress,
+        mut editor,
+        bindings,
+    } = screen_resources;
     if let Some(state_change) = event_reader.latest(&events) {
         match state_change.0 {
             AppState::Level(level_index) => {
-                for ent in ui_objects.iter() {
-                    commands.despawn_recursive(ent);
-                }
+                let level = match packs.level(level_index) {
+                    Some(level) => level,
+                    None => return,
+                };
 
-                for ent in level_objects.iter() {
-                    commands.despawn_recursive(ent);
+                screen_objects.despawn(commands);
+
+                match map::try_load_level(&level.file, commands, &mut level_resources) {
+                    Ok(description) => {
+                        let title = hud::level_title(&packs, level_index);
+                        hud::spawn_hud(
+                            commands,
+                            &asset_server,
+                            &mut color_materials,
+                            &title,
+                            &description.info,
+                            progress.best_moves(&level.file),
+                        );
+
+                        progress.last_level = Some(level.file.clone());
+                        if let Err(err) = progress.save() {
+                            eprintln!("{}", err);
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        startup_systems::load_level_selector(
+                            commands,
+                            asset_server,
+                            color_materials,
+                            &packs,
+                            &progress,
+                            Some(&err),
+                        );
+                        *state = AppState::LevelSelect;
+                        return;
+                    }
                 }
+            }
+            AppState::LevelSelect => {
+                screen_objects.despawn(commands);
 
-                turn_counter.0 = 0;
-                undo_buffer.0.clear();
+                startup_systems::load_level_selector(
+                    commands,
+                    asset_server,
+                    color_materials,
+                    &packs,
+                    &progress,
+                    None,
+                );
+            }
+            AppState::Editor => {
+                screen_objects.despawn(commands);
 
-                map::load_level(
-                    std::path::Path::new(LEVELS[level_index]),
+                // Editing from a level opens that level, otherwise a new one is started.
+                *editor = match *state {
+                    AppState::Level(_) | AppState::LevelCompleted(_) => Editor::open(
+                        level_resources.recorder.0.level.clone(),
+                        level_resources.level.clone(),
+                    ),
+                    _ => Editor::default(),
+                };
+                editor::spawn_editor(
                     commands,
-                    &materials,
-                    &mut meshes,
-                    &mut level_size,
+                    &asset_server,
+                    &mut color_materials,
+                    &editor,
+                    &mut level_resources,
                 );
             }
-            AppState::LevelSelect => {
-                for ent in level_objects.iter() {
-                    commands.despawn_recursive(ent);
-                }
+            AppState::Controls => {
+                screen_objects.despawn(commands);
 
-                startup_systems::load_level_selector(commands, asset_server, color_materials);
+                controls::spawn_controls(commands, &asset_server, &mut color_materials, &bindings);
             }
+            // Only entered by finishing a level, see `post_level_update::level_completed`.
+            AppState::LevelCompleted(_) => return,
         }
         *state = state_change.0;
     }
@@ -1,7 +1,15 @@
 use bevy::prelude::*;
 
+pub mod controls;
+
+pub mod editor;
+
 pub mod laser;
 
+pub mod hot_reload;
+
+pub mod hud;
+
 pub mod input;
 
 pub mod tracking;
@@ -12,12 +20,24 @@ pub mod post_level_update;
 
 pub mod screen_transformations;
 
+pub mod sync;
+
+pub mod turn;
+
+pub mod walk;
+
 pub struct SystemStagesPlugin;
 
 impl Plugin for SystemStagesPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_stage_after(stage::UPDATE, input::NAME, input::stage());
-        app.add_stage_after(input::NAME, "tracking-1", tracking::stage());
+        app.add_stage_after(input::NAME, walk::NAME, walk::stage());
+        app.add_stage_after(walk::NAME, hot_reload::NAME, hot_reload::stage());
+        app.add_stage_after(hot_reload::NAME, editor::NAME, editor::stage());
+        app.add_stage_after(editor::NAME, controls::NAME, controls::stage());
+        app.add_stage_after(controls::NAME, turn::NAME, turn::stage());
+        app.add_stage_after(turn::NAME, sync::NAME, sync::stage());
+        app.add_stage_after(sync::NAME, "tracking-1", tracking::stage());
 
         app.add_stage_after("tracking-1", laser::NAME, laser::stage());
         app.add_stage_after(laser::NAME, "tracking-2", tracking::stage());
@@ -30,8 +50,10 @@ impl Plugin for SystemStagesPlugin {
             post_level_update::stage(),
         );
 
+        app.add_stage_after(post_level_update::NAME, hud::NAME, hud::stage());
+
         app.add_stage_after(
-            post_level_update::NAME,
+            hud::NAME,
             screen_transformations::NAME,
             screen_transformations::stage(),
         );
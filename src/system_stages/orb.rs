@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 
+use crate::game_state::ObjectKind;
 use crate::*;
 
 pub const NAME: &str = "orb";
@@ -7,87 +8,100 @@ pub const NAME: &str = "orb";
 pub fn stage() -> SystemStage {
     let mut stage = SystemStage::parallel();
     stage.add_system(orb_update.system());
+    stage.add_system(gate_update.system());
+    stage.add_system(laser_source_update.system());
+    stage.add_system(plate_update.system());
     stage
 }
 
 pub fn orb_update(
     state: Res<AppState>,
-    keyboard_input: Res<Input<KeyCode>>,
+    game_state: ChangedRes<GameState>,
     materials: Res<Materials>,
-    turn_counter: Res<TurnCounter>,
-    mut undo_buffer: ResMut<UndoBuffer>,
-    laser_changed: Query<(), Changed<Laser>>,
-    laser_q: Query<&Laser>,
-    mut orb_q: Query<(Entity, &mut Orb, &Coordinate, &mut Handle<ColorMaterial>)>,
+    mut orb_q: Query<(&ObjectId, &mut Orb, &mut Handle<ColorMaterial>)>,
 ) {
     match *state {
         AppState::Level(_) => (),
         _ => return,
     }
 
-    if laser_changed.iter().next().is_none() {
-        return;
-    }
-
-    let should_push_undo_buffer = !keyboard_input.just_pressed(KeyCode::Z);
-
-    'outer: for (entity, mut orb, coord, mut material) in orb_q.iter_mut() {
-        let original_material = material.clone();
-        let original_state = orb.state;
-
-        let undo_fn = Box::new(move |world: &mut World| {
-            if let Ok(mut orb) = world.get_mut::<Orb>(entity) {
-                orb.state = original_state;
-            }
-
-            if let Ok(mut material) = world.get_mut::<Handle<ColorMaterial>>(entity) {
-                *material = original_material;
+    for (id, mut orb, mut material) in orb_q.iter_mut() {
+        if let ObjectKind::Orb { state, .. } = game_state.object(*id).kind {
+            if orb.state != state {
+                orb.state = state;
+                *material = materials.orb(orb.orb_type, state);
             }
-        });
+        }
+    }
+}
 
-        let (deactivated, activated, destroyed) = match orb.orb_type {
-            LaserType::Red => (
-                materials.orb_red_deactivated.clone(),
-                materials.orb_red_activated.clone(),
-                materials.orb_red_destroyed.clone(),
-            ),
-            LaserType::Blue => (
-                materials.orb_blue_deactivated.clone(),
-                materials.orb_blue_activated.clone(),
-                materials.orb_blue_destroyed.clone(),
-            ),
-            LaserType::Purple => (
-                materials.orb_purple_deactivated.clone(),
-                materials.orb_purple_activated.clone(),
-                materials.orb_purple_destroyed.clone(),
-            ),
-        };
+/// Closed gates block beams and pushes like walls, while open ones can be walked and pushed into.
+pub fn gate_update(
+    commands: &mut Commands,
+    state: Res<AppState>,
+    game_state: ChangedRes<GameState>,
+    materials: Res<Materials>,
+    mut gate_q: Query<(Entity, &ObjectId, &mut Gate, &mut Handle<ColorMaterial>)>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
 
-        for laser in laser_q.iter() {
-            if laser.end == *coord {
-                if laser.laser_type == orb.orb_type && orb.state != OrbState::Destroyed {
-                    orb.state = OrbState::Activated;
-                    *material = activated;
+    for (entity, id, mut gate, mut material) in gate_q.iter_mut() {
+        if let ObjectKind::Gate { open } = game_state.object(*id).kind {
+            if gate.open != open {
+                gate.open = open;
+                *material = materials.gate(open);
+                if open {
+                    commands.remove::<(Opaque, Movable)>(entity);
                 } else {
-                    orb.state = OrbState::Destroyed;
-                    *material = destroyed;
+                    commands.insert(entity, (Opaque, Movable(false)));
                 }
+            }
+        }
+    }
+}
 
-                if should_push_undo_buffer {
-                    undo_buffer.0.push((turn_counter.0, undo_fn));
-                }
+/// A switched off source keeps its place but stops lighting up, and its beam is dropped with the
+/// rest of the laser paths.
+pub fn laser_source_update(
+    state: Res<AppState>,
+    game_state: ChangedRes<GameState>,
+    materials: Res<Materials>,
+    mut source_q: Query<(&ObjectId, &mut LaserSource, &mut Handle<ColorMaterial>)>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
 
-                continue 'outer;
+    for (id, mut source, mut material) in source_q.iter_mut() {
+        if let ObjectKind::LaserSource { on, .. } = game_state.object(*id).kind {
+            if source.on != on {
+                source.on = on;
+                *material = materials.laser_source(source.laser_type, source.direction, on);
             }
         }
+    }
+}
 
-        if orb.state != OrbState::Destroyed {
-            *material = deactivated;
-            orb.state = OrbState::Deactivated;
+pub fn plate_update(
+    state: Res<AppState>,
+    game_state: ChangedRes<GameState>,
+    materials: Res<Materials>,
+    mut plate_q: Query<(&ObjectId, &mut Plate, &mut Handle<ColorMaterial>)>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
 
-            if should_push_undo_buffer {
-                undo_buffer.0.push((turn_counter.0, undo_fn));
-            }
+    for (id, mut plate, mut material) in plate_q.iter_mut() {
+        let pressed = game_state.is_pressed(*id);
+        if plate.pressed != pressed {
+            plate.pressed = pressed;
+            *material = materials.plate(pressed);
         }
     }
 }
@@ -11,24 +11,37 @@ pub fn stage() -> SystemStage {
 }
 
 pub fn level_completed(
-    state: Res<AppState>,
+    mut state: ResMut<AppState>,
+    game_state: Res<GameState>,
+    turn_counter: Res<TurnCounter>,
+    packs: Res<LevelPacks>,
+    mut progress: ResMut<Progress>,
+    mut completed_events: ResMut<Events<LevelCompletedEvent>>,
     laser_changed: Query<(), Changed<Laser>>,
-    orbs: Query<&Orb>,
 ) {
-    match *state {
-        AppState::Level(_) => (),
+    let level = match *state {
+        AppState::Level(level) => level,
         _ => return,
-    }
+    };
 
     if laser_changed.iter().next().is_none() {
         return;
     }
 
-    for orb in orbs.iter() {
-        if orb.state != OrbState::Activated {
-            return;
+    if !game_state.is_complete() {
+        return;
+    }
+
+    if let Some(entry) = packs.level(level) {
+        progress.record_completion(&entry.file, turn_counter.0);
+        if let Err(err) = progress.save() {
+            eprintln!("{}", err);
         }
     }
 
-    println!("Level Complete");
+    *state = AppState::LevelCompleted(level);
+    completed_events.send(LevelCompletedEvent {
+        level,
+        moves: turn_counter.0,
+    });
 }
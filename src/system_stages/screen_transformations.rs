@@ -18,7 +18,7 @@ fn size_scaling(
     mut q: Query<(&crate::Size, &mut Sprite)>,
 ) {
     match *state {
-        AppState::Level(_) => (),
+        AppState::Level(_) | AppState::LevelCompleted(_) | AppState::Editor => (),
         _ => return,
     }
 
@@ -39,7 +39,7 @@ fn position_translation(
     mut q: Query<(&Coordinate, &mut Transform)>,
 ) {
     match *state {
-        AppState::Level(_) => (),
+        AppState::Level(_) | AppState::LevelCompleted(_) | AppState::Editor => (),
         _ => return,
     }
 
@@ -54,7 +54,7 @@ fn position_translation(
     }
 }
 
-pub fn get_tile_size(window: &Window, level_size: &Res<LevelSize>) -> Vec2 {
+pub fn get_tile_size(window: &Window, level_size: &LevelSize) -> Vec2 {
     let tile_width = window.width() as f32 / (level_size.width as f32);
     let tile_height = window.height() as f32 / (level_size.height as f32);
     let min = tile_height.min(tile_width);
@@ -64,10 +64,26 @@ pub fn get_tile_size(window: &Window, level_size: &Res<LevelSize>) -> Vec2 {
 pub fn coordinate_to_screen_space(
     coord: Coordinate,
     window: &Window,
-    level_size: &Res<LevelSize>,
+    level_size: &LevelSize,
 ) -> Vec2 {
     let tile_size = get_tile_size(window, level_size);
     let bottom_left = Vec2::new(window.width() as f32 / -2.0, window.height() / -2.0 as f32);
     let center_adjustment = tile_size / 2.0;
     bottom_left + coord.scale(tile_size) + center_adjustment
 }
+
+/// The tile under a position in window space, where the bottom left of the window is the origin.
+pub fn screen_to_coordinate(
+    position: Vec2,
+    window: &Window,
+    level_size: &LevelSize,
+) -> Option<Coordinate> {
+    let tile_size = get_tile_size(window, level_size);
+    let x = (position.x / tile_size.x).floor() as i32;
+    let y = (position.y / tile_size.y).floor() as i32;
+    if x < 0 || y < 0 || x >= level_size.width as i32 || y >= level_size.height as i32 {
+        return None;
+    }
+
+    Some(Coordinate { x, y })
+}
@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+pub const NAME: &str = "sync";
+
+pub fn stage() -> SystemStage {
+    let mut stage = SystemStage::parallel();
+    stage.add_system(sync_coordinates.system());
+    stage.add_system(sync_jimbo_facing.system());
+    stage
+}
+
+fn sync_coordinates(
+    state: Res<AppState>,
+    game_state: Res<GameState>,
+    mut q: Query<(&ObjectId, &mut Coordinate)>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
+
+    for (id, mut coordinate) in q.iter_mut() {
+        let model_coordinate = game_state.object(*id).coordinate;
+        if *coordinate != model_coordinate {
+            *coordinate = model_coordinate;
+        }
+    }
+}
+
+fn sync_jimbo_facing(
+    state: Res<AppState>,
+    game_state: Res<GameState>,
+    materials: Res<Materials>,
+    mut q: Query<&mut Handle<ColorMaterial>, With<Jimbo>>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
+
+    let facing_material = materials.jimbo(game_state.facing());
+    for mut material in q.iter_mut() {
+        if *material != facing_material {
+            *material = facing_material.clone();
+        }
+    }
+}
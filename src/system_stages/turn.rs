@@ -0,0 +1,72 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+pub const NAME: &str = "turn";
+
+pub fn stage() -> SystemStage {
+    let mut stage = SystemStage::parallel();
+    stage.add_system(apply_level_commands.system());
+    stage
+}
+
+fn apply_level_commands(
+    state: Res<AppState>,
+    mut command_reader: Local<EventReader<LevelCommand>>,
+    level_commands: Res<Events<LevelCommand>>,
+    mut level: map::LevelResources,
+) {
+    for command in command_reader.iter(&level_commands) {
+        match *state {
+            AppState::Level(_) => (),
+            _ => continue,
+        }
+
+        level.recorder.0.commands.push(*command);
+
+        match *command {
+            LevelCommand::Move(direction) => {
+                let before = snapshot(&level);
+                level.undo_buffer.0.push(before);
+                level.redo_buffer.0.clear();
+                let outcome = level.game_state.step(direction);
+                level.turn_counter.0 += 1;
+                if let Some(letter) = lurd::step_char(&outcome) {
+                    level.move_log.push(letter);
+                }
+            }
+            LevelCommand::Restart => {
+                let before = snapshot(&level);
+                level.undo_buffer.0.push(before);
+                level.redo_buffer.0.clear();
+                *level.game_state = GameState::new(&level.level);
+                level.turn_counter.0 = 0;
+                level.move_log.restart();
+            }
+            LevelCommand::Undo => {
+                if let Some(previous) = level.undo_buffer.0.pop() {
+                    let before = snapshot(&level);
+                    level.redo_buffer.0.push(before);
+                    restore(&mut level, previous);
+                }
+            }
+            LevelCommand::Redo => {
+                if let Some(next) = level.redo_buffer.0.pop() {
+                    let before = snapshot(&level);
+                    level.undo_buffer.0.push(before);
+                    restore(&mut level, next);
+                }
+            }
+        }
+    }
+}
+
+fn snapshot(level: &map::LevelResources) -> TurnSnapshot {
+    TurnSnapshot::new(&level.turn_counter, &level.move_log, &level.game_state)
+}
+
+fn restore(level: &mut map::LevelResources, snapshot: TurnSnapshot) {
+    level.game_state.restore(&snapshot.state);
+    level.turn_counter.0 = snapshot.turn;
+    level.move_log.restore(snapshot.moves);
+}
@@ -0,0 +1,177 @@
+use bevy::ecs::SystemParam;
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+use crate::replay::Playback;
+use crate::system_stages::screen_transformations::screen_to_coordinate;
+use crate::*;
+
+pub const NAME: &str = "walk";
+
+/// Walks are quicker than replays since they only stand in for pressing the arrow keys.
+pub const WALK_MOVES_PER_SECOND: f32 = 12.0;
+
+pub fn stage() -> SystemStage {
+    let mut stage = SystemStage::parallel();
+    stage.add_system(click_to_move.system());
+    stage.add_system(walk.system());
+    stage
+}
+
+/// The steps left on the way to a clicked tile. Each one is sent as its own move, so a walk is
+/// undone and scored step by step like moves made with the keys.
+#[derive(Debug, Clone)]
+pub struct Walk(pub Playback);
+
+impl Default for Walk {
+    fn default() -> Self {
+        Walk(Playback {
+            moves_per_second: WALK_MOVES_PER_SECOND,
+            ..Default::default()
+        })
+    }
+}
+
+/// What's needed to find the tile a click landed on, and what's there.
+#[derive(SystemParam)]
+pub struct LevelClick<'a> {
+    pub mouse_input: Res<'a, Input<MouseButton>>,
+    pub windows: Res<'a, Windows>,
+    pub level_size: Res<'a, LevelSize>,
+    pub tracker: Res<'a, EntityTracker>,
+}
+
+fn click_to_move(
+    state: Res<AppState>,
+    click: LevelClick,
+    playback: Res<Playback>,
+    mut walk: ResMut<Walk>,
+    jimbo_q: Query<&Coordinate, With<Jimbo>>,
+    object_q: Query<(Option<&Tile>, Option<&Movable>)>,
+    interaction_q: Query<&Interaction>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => return,
+    }
+
+    if !click.mouse_input.just_pressed(MouseButton::Left) || playback.is_playing() {
+        return;
+    }
+
+    // A new click replaces the walk under way, and one on the HUD is meant for its buttons rather
+    // than the tile under them.
+    walk.0.stop();
+    if interaction_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+
+    let window = click.windows.get_primary().unwrap();
+    let target = match window
+        .cursor_position()
+        .and_then(|position| screen_to_coordinate(position, window, &click.level_size))
+    {
+        Some(target) => target,
+        None => return,
+    };
+    let jimbo = match jimbo_q.iter().next() {
+        Some(jimbo) => *jimbo,
+        None => return,
+    };
+
+    // Floor with nothing on it that could be pushed or would block.
+    let walkable = |coordinate: Coordinate| match click.tracker.0.get(&coordinate) {
+        Some(entities) => {
+            let mut floor = false;
+            for entity in entities {
+                match object_q.get(*entity) {
+                    Ok((_, Some(_))) => return false,
+                    Ok((Some(_), None)) => floor = true,
+                    _ => (),
+                }
+            }
+            floor
+        }
+        None => false,
+    };
+
+    if let Some(path) = find_path(jimbo, target, walkable) {
+        walk.0.play(path.into_iter().map(LevelCommand::Move));
+    }
+}
+
+fn walk(
+    state: Res<AppState>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut walk: ResMut<Walk>,
+    mut level_commands: ResMut<Events<LevelCommand>>,
+) {
+    match *state {
+        AppState::Level(_) => (),
+        _ => {
+            walk.0.stop();
+            return;
+        }
+    }
+
+    // Any key takes over from the walk.
+    if keyboard_input.get_just_pressed().next().is_some() {
+        walk.0.stop();
+        return;
+    }
+
+    if let Some(command) = walk.0.tick(time.delta_seconds()) {
+        level_commands.send(command);
+    }
+}
+
+/// The shortest path from `from` to `to` over walkable tiles, or `None` if there isn't one. `to` has
+/// to be walkable itself, while `from` is where Jimbo already stands.
+pub fn find_path(
+    from: Coordinate,
+    to: Coordinate,
+    walkable: impl Fn(Coordinate) -> bool,
+) -> Option<Vec<crate::Direction>> {
+    if from == to || !walkable(to) {
+        return None;
+    }
+
+    let directions = [
+        crate::Direction::Up,
+        crate::Direction::Right,
+        crate::Direction::Down,
+        crate::Direction::Left,
+    ];
+
+    // The step taken into each reached tile, followed back from `to` once it's found.
+    let mut came_by = HashMap::new();
+    let mut frontier = VecDeque::new();
+    frontier.push_back(from);
+    while let Some(current) = frontier.pop_front() {
+        if current == to {
+            let mut path = Vec::new();
+            let mut at = to;
+            while at != from {
+                let direction: crate::Direction = came_by[&at];
+                path.push(direction);
+                at -= direction.direction();
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for direction in directions.iter() {
+            let next = current + direction.direction();
+            if next != from && !came_by.contains_key(&next) && walkable(next) {
+                came_by.insert(next, *direction);
+                frontier.push_back(next);
+            }
+        }
+    }
+
+    None
+}